@@ -0,0 +1,43 @@
+use std::io::{BufReader, stdin, stdout};
+
+use serde_path::{
+    FilterChain, hlist, json_ser::SerWrapper, map::MapValues, predicate::Has, select::Select,
+};
+
+fn main() {
+    // equivalent to `.services | map_values(select(has("ports")) | .image)` in yq.
+    let path = hlist!["services", MapValues(hlist![Select(Has("ports")), "image"])];
+
+    let mut ser = serde_yaml::Serializer::new(stdout());
+    for de in serde_yaml::Deserializer::from_reader(BufReader::new(stdin())) {
+        path.filter(SerWrapper(&mut ser), de).unwrap();
+    }
+}
+
+#[test]
+fn test() {
+    use std::io::Cursor;
+
+    let stdin = Cursor::new(
+        "
+services:
+  web:
+    image: nginx
+    ports: [80]
+  db:
+    image: postgres
+---
+services: {}
+",
+    );
+
+    // equivalent to `.services | map_values(select(has("ports")) | .image)` in yq.
+    let path = hlist!["services", MapValues(hlist![Select(Has("ports")), "image"])];
+
+    let mut ser = serde_yaml::Serializer::new(Vec::new());
+    for de in serde_yaml::Deserializer::from_reader(stdin) {
+        path.filter(SerWrapper(&mut ser), de).unwrap();
+    }
+    let output = String::from_utf8(ser.into_inner().unwrap()).unwrap();
+    assert_eq!(output, "web: nginx\n--- {}\n");
+}
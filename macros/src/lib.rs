@@ -0,0 +1,680 @@
+use proc_macro2::{Delimiter, Group, Ident, Spacing, Span, TokenStream, TokenTree};
+use quote::{ToTokens, quote};
+use syn::{Error, Lit, LitFloat, LitInt, LitStr};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Compiles a jq filter into a static `hlist!` chain.
+///
+/// Supported: `.key`, `."key"`, `.[0]`, `.[-1]`, `.[1:-1]`, `.[]`, `..`, `?`, `|`, `(...)`,
+/// `map(f)` and `select(f op literal)` with `==`, `!=`, `<`, `<=`, `>` or `>=`. Comparisons
+/// `has("key")`, `has(0)` and `f | startswith("a")`, `endswith` or `contains` combine with
+/// `and`, `or`, `| not` and parentheses. `.[]` and `..` apply the rest of the pipeline to every
+/// element or nested value and collect the results into a sequence, like `map`.
+///
+/// Literals compare against a value of the same type: strings as `String`, numbers as
+/// `serde_path::predicate::Number`, booleans as `bool`. `null` matches a missing or null value of
+/// any type, and can only be compared with `==` or `!=`.
+#[proc_macro]
+pub fn path(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut parser = Parser::new(input.into(), Span::call_site());
+    let stages = parser.pipeline().and_then(|stages| {
+        parser.end()?;
+        Ok(stages)
+    });
+    match stages {
+        Ok(stages) => chain(&stages).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Clone)]
+enum Stage {
+    Key(LitStr),
+    Index(LitInt),
+    FromEnd(LitInt),
+    Slice(TokenStream),
+    Each,
+    Recurse,
+    Optional(Vec<Stage>),
+    Map(Vec<Stage>),
+    Select(Predicate),
+}
+
+#[derive(Clone)]
+enum Predicate {
+    Compare {
+        filter: Vec<Stage>,
+        op: Ident,
+        value: TokenStream,
+    },
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+    /// `has(...)` or a string test, already expanded.
+    Call(TokenStream),
+}
+
+/// Builtins that test a string, `.name | startswith("a")`.
+const STRING_TESTS: &[&str] = &["contains", "startswith", "endswith", "test"];
+
+/// Where a `select` pipeline ends and its test begins.
+const TESTS: &[&str] = &["not", "contains", "startswith", "endswith", "test"];
+
+fn predicate(p: &Predicate) -> TokenStream {
+    match p {
+        Predicate::Compare { filter, op, value } => {
+            let filter = chain(filter);
+            quote!(::serde_path::predicate::#op::new(#filter, #value))
+        }
+        Predicate::And(a, b) => {
+            let (a, b) = (predicate(a), predicate(b));
+            quote!(::serde_path::predicate::And(#a, #b))
+        }
+        Predicate::Or(a, b) => {
+            let (a, b) = (predicate(a), predicate(b));
+            quote!(::serde_path::predicate::Or(#a, #b))
+        }
+        Predicate::Not(p) => {
+            let p = predicate(p);
+            quote!(::serde_path::predicate::Not(#p))
+        }
+        Predicate::Call(call) => call.clone(),
+    }
+}
+
+fn chain(stages: &[Stage]) -> TokenStream {
+    let Some((first, rest)) = stages.split_first() else {
+        return quote!(::serde_path::Final);
+    };
+    let head = match first {
+        Stage::Key(key) => quote!(#key),
+        Stage::Index(index) => quote!(#index),
+        Stage::FromEnd(index) => quote!(::serde_path::FromEnd::new(#index).unwrap()),
+        Stage::Slice(slice) => quote!(#slice),
+        Stage::Each => {
+            let inner = chain(rest);
+            return quote!((::serde_path::map::Map(#inner), ::serde_path::Final));
+        }
+        Stage::Recurse => {
+            let inner = chain(rest);
+            return quote!((::serde_path::map::Recurse::new(#inner), ::serde_path::Final));
+        }
+        Stage::Map(inner) => {
+            let inner = chain(inner);
+            quote!(::serde_path::map::Map(#inner))
+        }
+        Stage::Optional(inner) => {
+            let inner = chain(inner);
+            quote!(::serde_path::optional::Optional(#inner))
+        }
+        Stage::Select(p) => {
+            let p = predicate(p);
+            quote!(::serde_path::select::Select(#p))
+        }
+    };
+    let rest = chain(rest);
+    quote!((#head, #rest))
+}
+
+struct Parser {
+    tokens: Vec<TokenTree>,
+    pos: usize,
+    /// reported for errors at the end of the input.
+    end_span: Span,
+    /// the filter piped into the condition being parsed, `.a` in `select(.a | .b == 1)`.
+    prefix: Vec<Stage>,
+}
+
+impl Parser {
+    fn new(tokens: TokenStream, end_span: Span) -> Self {
+        Self {
+            tokens: tokens.into_iter().collect(),
+            pos: 0,
+            end_span,
+            prefix: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<&TokenTree> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&TokenTree> {
+        self.tokens.get(self.pos + n)
+    }
+
+    fn span(&self) -> Span {
+        self.peek().map_or(self.end_span, TokenTree::span)
+    }
+
+    fn is_punct(&self, n: usize, c: char) -> bool {
+        matches!(self.peek_nth(n), Some(TokenTree::Punct(p)) if p.as_char() == c)
+    }
+
+    fn end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.unexpected("`|` or the end of the filter")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let Some(token) = self.peek() else {
+            return Error::new(self.end_span, format!("expected {expected}"));
+        };
+        if let TokenTree::Punct(p) = token {
+            let op = match p.as_char() {
+                '=' | '!' | '<' | '>' if self.is_punct(1, '=') => Some(format!("{}=", p.as_char())),
+                '<' | '>' | '+' | '-' | '*' | '/' | '%' => Some(p.as_char().to_string()),
+                ',' => Some(",".to_string()),
+                _ => None,
+            };
+            if let Some(op) = op {
+                return Error::new(p.span(), format!("unsupported operator `{op}` in `path!`"));
+            }
+        }
+        if let TokenTree::Ident(ident) = token
+            && (ident == "and" || ident == "or")
+        {
+            return Error::new(
+                ident.span(),
+                format!("unsupported operator `{ident}` in `path!`"),
+            );
+        }
+        Error::new(token.span(), format!("expected {expected}"))
+    }
+
+    fn pipeline(&mut self) -> Result<Vec<Stage>> {
+        let mut stages = Vec::new();
+        loop {
+            stages.extend(self.term()?);
+            if !self.is_punct(0, '|') || self.at_pipe_into(TESTS) {
+                return Ok(stages);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn term(&mut self) -> Result<Vec<Stage>> {
+        let mut stages = Vec::new();
+        match self.peek().cloned() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '.' => {
+                self.pos += 1;
+                if p.spacing() == Spacing::Joint && self.is_punct(0, '.') {
+                    self.pos += 1;
+                    stages.push(Stage::Recurse);
+                } else {
+                    self.key(&mut stages)?;
+                }
+            }
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                self.pos += 1;
+                let mut inner = Parser::new(group.stream(), group.span_close());
+                stages = inner.pipeline()?;
+                inner.end()?;
+            }
+            Some(TokenTree::Ident(ident)) => {
+                self.pos += 1;
+                let args = match self.peek().cloned() {
+                    Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+                        self.pos += 1;
+                        g
+                    }
+                    _ if ident == "true" || ident == "false" || ident == "null" => {
+                        return Err(Error::new(
+                            ident.span(),
+                            "literals are only supported in comparisons",
+                        ));
+                    }
+                    _ if ident != "map" && ident != "select" => {
+                        return Err(Error::new(
+                            ident.span(),
+                            format!("unsupported function `{ident}` in `path!`"),
+                        ));
+                    }
+                    _ => return Err(self.unexpected("`(`")),
+                };
+                let mut inner = Parser::new(args.stream(), args.span_close());
+                if ident == "map" {
+                    stages.push(Stage::Map(inner.pipeline()?));
+                } else if ident == "select" {
+                    stages.push(Stage::Select(inner.condition()?));
+                } else {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("unsupported function `{ident}` in `path!`"),
+                    ));
+                }
+                inner.end()?;
+            }
+            Some(TokenTree::Literal(lit)) => {
+                return Err(Error::new(
+                    lit.span(),
+                    "literals are only supported in comparisons",
+                ));
+            }
+            _ => return Err(self.unexpected("a filter")),
+        }
+
+        // where the stages of the last primary or suffix start, for `?`.
+        let mut last = 0;
+        loop {
+            match self.peek() {
+                Some(TokenTree::Punct(p)) if p.as_char() == '?' => {
+                    let span = p.span();
+                    self.pos += 1;
+                    let inner = stages.split_off(last);
+                    if inner
+                        .iter()
+                        .any(|stage| matches!(stage, Stage::Each | Stage::Recurse))
+                    {
+                        return Err(Error::new(
+                            span,
+                            "`?` applied to multiple outputs is not supported in `path!`",
+                        ));
+                    }
+                    stages.push(Stage::Optional(inner));
+                }
+                Some(TokenTree::Punct(p)) if p.as_char() == '.' => {
+                    let span = p.span();
+                    self.pos += 1;
+                    last = stages.len();
+                    if self.is_punct(0, '.') {
+                        return Err(Error::new(
+                            span,
+                            "`..` must start a filter, as in `.a | ..`",
+                        ));
+                    }
+                    if !self.key(&mut stages)? {
+                        return Err(self.unexpected("a key"));
+                    }
+                }
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+                    last = stages.len();
+                    self.key(&mut stages)?;
+                }
+                _ => return Ok(stages),
+            }
+        }
+    }
+
+    /// What follows a `.`: `foo`, `"foo"` or `[...]`. Also used for bare `[...]` suffixes.
+    fn key(&mut self, stages: &mut Vec<Stage>) -> Result<bool> {
+        match self.peek().cloned() {
+            Some(TokenTree::Ident(ident)) => {
+                self.pos += 1;
+                let name = ident.to_string();
+                let name = name.strip_prefix("r#").unwrap_or(&name);
+                stages.push(Stage::Key(LitStr::new(name, ident.span())));
+            }
+            Some(TokenTree::Literal(lit)) => {
+                self.pos += 1;
+                match Lit::new(lit) {
+                    Lit::Str(key) => stages.push(Stage::Key(key)),
+                    lit => return Err(Error::new(lit.span(), "expected a key")),
+                }
+            }
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket => {
+                self.pos += 1;
+                stages.push(bracket(&group)?);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The argument of `select`: comparisons combined with `and`, `or`, `| not` and parentheses.
+    ///
+    /// `|` binds loosest, like in jq: `.a | .b == 1 or .c == 2` tests `.a.b` and `.a.c`.
+    fn condition(&mut self) -> Result<Predicate> {
+        let start = self.pos;
+        if let Ok(Operand::Filter(filter)) = self.operand()
+            && self.is_punct(0, '|')
+            && !self.at_pipe_into(&["not"])
+        {
+            self.pos += 1;
+            let prefix = std::mem::replace(&mut self.prefix, filter);
+            let predicate = self.condition();
+            self.prefix = prefix;
+            return predicate;
+        }
+        self.pos = start;
+
+        let mut lhs = self.conjunction()?;
+        while self.eat_keyword("or") {
+            let rhs = self.conjunction()?;
+            lhs = Predicate::Or(Box::new(lhs), Box::new(rhs));
+        }
+        while self.at_pipe_into(&["not"]) {
+            self.pos += 2;
+            lhs = Predicate::Not(Box::new(lhs));
+        }
+        Ok(lhs)
+    }
+
+    fn conjunction(&mut self) -> Result<Predicate> {
+        let mut lhs = self.grouped()?;
+        while self.eat_keyword("and") {
+            let rhs = self.grouped()?;
+            lhs = Predicate::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `(condition)`, or a comparison if the parentheses group a filter instead.
+    fn grouped(&mut self) -> Result<Predicate> {
+        if let Some(TokenTree::Group(group)) = self.peek()
+            && group.delimiter() == Delimiter::Parenthesis
+        {
+            let mut inner = Parser::new(group.stream(), group.span_close());
+            inner.prefix = self.prefix.clone();
+            let grouped = inner.condition().and_then(|p| inner.end().map(|()| p));
+            match grouped {
+                Ok(predicate) => {
+                    self.pos += 1;
+                    return Ok(predicate);
+                }
+                Err(err) => return self.compare().map_err(|_| err),
+            }
+        }
+        if let Some(TokenTree::Ident(ident)) = self.peek()
+            && ident == "has"
+            && let Some(TokenTree::Group(args)) = self.peek_nth(1)
+            && args.delimiter() == Delimiter::Parenthesis
+        {
+            if !self.prefix.is_empty() {
+                return Err(Error::new(
+                    ident.span(),
+                    "`has` after `|` is not supported in `path!`",
+                ));
+            }
+            let has = has(args)?;
+            self.pos += 2;
+            return Ok(Predicate::Call(has));
+        }
+        if matches!(self.peek(), Some(TokenTree::Ident(ident)) if STRING_TESTS.iter().any(|t| ident == t))
+        {
+            return self.string_test(self.prefix.clone());
+        }
+        self.compare()
+    }
+
+    /// A string test on `filter`: `startswith("a")`.
+    fn string_test(&mut self, filter: Vec<Stage>) -> Result<Predicate> {
+        let Some(TokenTree::Ident(name)) = self.peek().cloned() else {
+            return Err(self.unexpected("a string test"));
+        };
+        let test = match name.to_string().as_str() {
+            "contains" => "Contains",
+            "startswith" => "StartsWith",
+            "endswith" => "EndsWith",
+            _ => {
+                return Err(Error::new(
+                    name.span(),
+                    "regular expressions are not supported, use `serde_path::predicate::Matches`",
+                ));
+            }
+        };
+        let pattern = match self.peek_nth(1) {
+            Some(TokenTree::Group(args)) if args.delimiter() == Delimiter::Parenthesis => {
+                let mut tokens = args.stream().into_iter();
+                match (tokens.next(), tokens.next()) {
+                    (Some(TokenTree::Literal(lit)), None) => match Lit::new(lit) {
+                        Lit::Str(pattern) => pattern,
+                        lit => return Err(Error::new(lit.span(), "expected a string")),
+                    },
+                    _ => return Err(Error::new(args.span(), "expected a string")),
+                }
+            }
+            _ => return Err(Error::new(name.span(), "expected `(`")),
+        };
+        self.pos += 2;
+        let filter = chain(&filter);
+        let test = Ident::new(test, name.span());
+        Ok(Predicate::Call(
+            quote!(::serde_path::predicate::#test::new(#filter, #pattern)),
+        ))
+    }
+
+    /// Whether `| name` is next, for one of the names.
+    fn at_pipe_into(&self, names: &[&str]) -> bool {
+        self.is_punct(0, '|')
+            && matches!(self.peek_nth(1), Some(TokenTree::Ident(ident)) if names.iter().any(|n| ident == n))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(TokenTree::Ident(ident)) if ident == keyword => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// `filter op literal` or `literal op filter`.
+    fn compare(&mut self) -> Result<Predicate> {
+        let lhs = self.operand()?;
+        let op_span = self.span();
+        let (op, len) = match self.peek() {
+            Some(TokenTree::Punct(p)) if self.is_punct(1, '=') => match p.as_char() {
+                '=' => ("Eq", 2),
+                '!' => ("NotEq", 2),
+                '<' => ("Le", 2),
+                '>' => ("Ge", 2),
+                _ => return Err(self.unexpected("a comparison")),
+            },
+            Some(TokenTree::Punct(p)) if p.as_char() == '<' => ("Lt", 1),
+            Some(TokenTree::Punct(p)) if p.as_char() == '>' => ("Gt", 1),
+            None => return Err(Error::new(self.end_span, "`select` requires a comparison")),
+            Some(_) => return Err(self.unexpected("a comparison")),
+        };
+        self.pos += len;
+        let rhs = self.operand()?;
+
+        let (filter, value, op) = match (lhs, rhs) {
+            (Operand::Filter(filter), Operand::Literal(value)) => (filter, value, op),
+            // `1 < .a` is `.a > 1`
+            (Operand::Literal(value), Operand::Filter(filter)) => {
+                let op = match op {
+                    "Lt" => "Gt",
+                    "Gt" => "Lt",
+                    "Le" => "Ge",
+                    "Ge" => "Le",
+                    op => op,
+                };
+                (filter, value, op)
+            }
+            _ => {
+                return Err(Error::new(
+                    op_span,
+                    "a comparison needs one filter and one literal",
+                ));
+            }
+        };
+        if value.is_null && !matches!(op, "Eq" | "NotEq") {
+            return Err(Error::new(
+                op_span,
+                "`null` can only be compared with `==` or `!=`",
+            ));
+        }
+        Ok(Predicate::Compare {
+            filter,
+            op: Ident::new(op, op_span),
+            value: value.tokens,
+        })
+    }
+
+    /// A filter operand, after the filter piped into the condition.
+    fn filter(&mut self) -> Result<Operand> {
+        let mut filter = self.prefix.clone();
+        filter.extend(self.term()?);
+        Ok(Operand::Filter(filter))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        let negative = self.is_punct(0, '-');
+        let offset = usize::from(negative);
+        let literal = match self.peek_nth(offset).cloned() {
+            Some(TokenTree::Literal(lit)) => Lit::new(lit),
+            Some(TokenTree::Ident(ident)) if !negative => {
+                let tokens = match ident.to_string().as_str() {
+                    "true" => quote!(true),
+                    "false" => quote!(false),
+                    "null" => quote!(
+                        ::core::option::Option::None::<
+                            ::serde_path::__private::serde::de::IgnoredAny,
+                        >
+                    ),
+                    _ => return self.filter(),
+                };
+                self.pos += 1;
+                return Ok(Operand::Literal(Literal {
+                    is_null: ident == "null",
+                    tokens,
+                }));
+            }
+            _ if negative => return Err(self.unexpected("a number")),
+            _ => return self.filter(),
+        };
+        self.pos += 1 + offset;
+
+        let number = match literal {
+            Lit::Str(s) if !negative => {
+                return Ok(Operand::Literal(Literal {
+                    is_null: false,
+                    tokens: quote!(::std::string::String::from(#s)),
+                }));
+            }
+            Lit::Int(n) if n.suffix().is_empty() => number(n.base10_digits(), negative, n.span()),
+            Lit::Float(n) if n.suffix().is_empty() => {
+                let n = LitFloat::new(&format!("{}f64", n.base10_digits()), n.span());
+                if negative { quote!(-#n) } else { quote!(#n) }
+            }
+            lit => return Err(Error::new(lit.span(), "expected a string or a number")),
+        };
+        Ok(Operand::Literal(Literal {
+            is_null: false,
+            tokens: quote!(::serde_path::predicate::Number::from(#number)),
+        }))
+    }
+}
+
+enum Operand {
+    Filter(Vec<Stage>),
+    Literal(Literal),
+}
+
+struct Literal {
+    tokens: TokenStream,
+    is_null: bool,
+}
+
+/// A `u64` or an `i64` literal, or an `f64` if the integer fits neither.
+fn number(digits: &str, negative: bool, span: Span) -> TokenStream {
+    let (suffix, fits) = match negative {
+        false => ("u64", digits.parse::<u64>().is_ok()),
+        // `i64::MIN` has no positive counterpart to negate.
+        true => ("i64", digits.parse::<i64>().is_ok()),
+    };
+    if negative && !fits && format!("-{digits}").parse::<i64>().is_ok() {
+        return quote!(::core::primitive::i64::MIN);
+    }
+    let n = match fits {
+        true => LitInt::new(&format!("{digits}{suffix}"), span).into_token_stream(),
+        false => LitFloat::new(&format!("{digits}f64"), span).into_token_stream(),
+    };
+    if negative { quote!(-#n) } else { n }
+}
+
+/// The argument of `has`: a key or an index.
+fn has(args: &Group) -> Result<TokenStream> {
+    let mut tokens = args.stream().into_iter();
+    let (Some(TokenTree::Literal(lit)), None) = (tokens.next(), tokens.next()) else {
+        return Err(Error::new(args.span(), "expected a key or an index"));
+    };
+    match Lit::new(lit) {
+        Lit::Str(key) => Ok(quote!(::serde_path::predicate::Has(#key))),
+        Lit::Int(n) if n.suffix().is_empty() => {
+            let n = LitInt::new(&format!("{}usize", n.base10_digits()), n.span());
+            Ok(quote!(::serde_path::predicate::HasIndex(#n)))
+        }
+        lit => Err(Error::new(lit.span(), "expected a key or an index")),
+    }
+}
+
+/// `Some(-1isize)` or `None` for an omitted bound.
+fn slice_bound(tokens: &[TokenTree], group: &Group) -> Result<TokenStream> {
+    let (sign, lit) = match tokens {
+        [] => return Ok(quote!(::core::option::Option::None)),
+        [TokenTree::Literal(lit)] => ("", lit),
+        [TokenTree::Punct(p), TokenTree::Literal(lit)] if p.as_char() == '-' => ("-", lit),
+        _ => return Err(Error::new(group.span(), "expected an integer slice bound")),
+    };
+    match Lit::new(lit.clone()) {
+        Lit::Int(n) if n.suffix().is_empty() => {
+            let bound = format!("{sign}{}", n.base10_digits());
+            bound
+                .parse::<isize>()
+                .map_err(|_| Error::new(n.span(), "slice bound out of range"))?;
+            let n = LitInt::new(&format!("{}isize", n.base10_digits()), n.span());
+            let n = match sign {
+                "-" => quote!(-#n),
+                _ => quote!(#n),
+            };
+            Ok(quote!(::core::option::Option::Some(#n)))
+        }
+        lit => Err(Error::new(lit.span(), "expected an integer slice bound")),
+    }
+}
+
+/// `[]`, `["key"]`, `[0]`, `[-1]` or `[start:end]`.
+fn bracket(group: &Group) -> Result<Stage> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    match &tokens[..] {
+        [] => Ok(Stage::Each),
+        [TokenTree::Literal(lit)] => match Lit::new(lit.clone()) {
+            Lit::Str(key) => Ok(Stage::Key(key)),
+            Lit::Int(index) if index.suffix().is_empty() => {
+                index.base10_parse::<usize>()?;
+                let index = LitInt::new(&format!("{}usize", index.base10_digits()), index.span());
+                Ok(Stage::Index(index))
+            }
+            lit => Err(Error::new(lit.span(), "expected an array index or a key")),
+        },
+        _ if tokens
+            .iter()
+            .any(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ':')) =>
+        {
+            let mut parts =
+                tokens.split(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ':'));
+            let (Some(start), Some(end), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(Error::new(group.span(), "expected `[start:end]`"));
+            };
+            let start = slice_bound(start, group)?;
+            let end = slice_bound(end, group)?;
+            Ok(Stage::Slice(quote!(::serde_path::Slice {
+                start: #start,
+                end: #end,
+                step: 1,
+            })))
+        }
+        [TokenTree::Punct(p), TokenTree::Literal(lit)] if p.as_char() == '-' => {
+            match Lit::new(lit.clone()) {
+                Lit::Int(index) if index.suffix().is_empty() => {
+                    let n = index.base10_parse::<usize>()?;
+                    let index =
+                        LitInt::new(&format!("{}usize", index.base10_digits()), index.span());
+                    // `[-0]` is `[0]`, as in jq
+                    Ok(match n {
+                        0 => Stage::Index(index),
+                        _ => Stage::FromEnd(index),
+                    })
+                }
+                lit => Err(Error::new(lit.span(), "expected an array index")),
+            }
+        }
+        _ => Err(Error::new(group.span(), "expected an array index or a key")),
+    }
+}
@@ -0,0 +1,868 @@
+//! Filtering CBOR ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)) from a byte slice.
+//!
+//! Any CBOR deserializer works with the filters, which buffer values as
+//! [`Content`](crate::content::Content) when they need to read them twice. This one borrows
+//! strings and bytes from the input, and lets filters capture a value as [`RawCbor`]: the
+//! slice of its encoding, re-read without being decoded first.
+//!
+//! The decoder is the crate's own rather than ciborium's or minicbor's. [`RawCbor`] needs the
+//! span of each item in the input, and strings are borrowed from it, neither of which ciborium
+//! allows as it reads through `io::Read`. minicbor decodes through its own traits rather than
+//! serde's. It is tested against ciborium's encoder instead.
+//!
+//! Other simple values than `false`, `true`, `null` and `undefined` are read as their number.
+//!
+//! ```
+//! use serde_path::{FilterChain, cbor};
+//!
+//! // {"a": [1, 2, 3]}
+//! let input = [0xa1, 0x61, b'a', 0x83, 0x01, 0x02, 0x03];
+//! let mut de = cbor::Deserializer::from_slice(&input);
+//! let last: u8 = serde_path::path!(.a[-1])
+//!     .filter(std::marker::PhantomData::<u8>, &mut de)
+//!     .unwrap();
+//! de.end().unwrap();
+//! assert_eq!(last, 3);
+//! ```
+
+use std::{fmt, str};
+
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess,
+    value::{BorrowedBytesDeserializer, BorrowedStrDeserializer},
+};
+
+use crate::content::Content;
+
+/// The name a [`RawCbor`] asks the deserializer for, and the only key of the map it answers with.
+pub(crate) const TOKEN: &str = "$serde_path::private::RawCbor";
+
+/// How deep sequences, maps and tags can be nested, to keep the stack bounded.
+const RECURSION_LIMIT: usize = 128;
+
+const BREAK: u8 = 0xff;
+
+/// Deserializes a value from the whole slice.
+pub fn from_slice<'de, T>(input: &'de [u8]) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::from_slice(input);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+/// A CBOR data item, borrowed from the input without being decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawCbor<'de>(pub(crate) &'de [u8]);
+
+impl<'de> RawCbor<'de> {
+    /// The encoded item.
+    pub fn as_bytes(&self) -> &'de [u8] {
+        self.0
+    }
+}
+
+impl<'de> de::Deserialize<'de> for RawCbor<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, RawCborVisitor)
+    }
+}
+
+struct RawCborVisitor;
+
+impl<'de> de::Visitor<'de> for RawCborVisitor {
+    type Value = RawCbor<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a raw CBOR value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        match map.next_key::<Content>()? {
+            Some(key) if key.as_str() == Some(TOKEN) => map.next_value().map(RawCbor),
+            _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+/// Reads the value again, as the deserializer it was captured from would.
+impl<'de> de::Deserializer<'de> for RawCbor<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(&mut Deserializer::from_slice(self.0), visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_option(&mut Deserializer::from_slice(self.0), visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_newtype_struct(
+            &mut Deserializer::from_slice(self.0),
+            name,
+            visitor,
+        )
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_enum(
+            &mut Deserializer::from_slice(self.0),
+            name,
+            variants,
+            visitor,
+        )
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+/// A CBOR deserializer over a byte slice.
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn from_slice(input: &'de [u8]) -> Self {
+        Deserializer {
+            input,
+            offset: 0,
+            depth: 0,
+        }
+    }
+
+    /// Checks that the whole input was read.
+    pub fn end(&self) -> Result<(), Error> {
+        if self.offset < self.input.len() {
+            return Err(self.error("trailing data"));
+        }
+        Ok(())
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error(format!("{msg} at offset {}", self.offset))
+    }
+
+    fn peek(&self) -> Result<u8, Error> {
+        self.input
+            .get(self.offset)
+            .copied()
+            .ok_or_else(|| self.error("unexpected end of input"))
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'de [u8], Error> {
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.offset.checked_add(len))
+            .filter(|&end| end <= self.input.len())
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        let bytes = &self.input[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: u64) -> Result<u64, Error> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0, |n, &b| (n << 8) | u64::from(b)))
+    }
+
+    /// The major type and argument of the next item. The argument is `None` for an
+    /// indefinite length, and the raw bits of floats and simple values.
+    fn head(&mut self) -> Result<(u8, Option<u64>), Error> {
+        let initial = self.peek()?;
+        self.offset += 1;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let arg = match info {
+            0..=23 => Some(u64::from(info)),
+            24 => Some(self.uint(1)?),
+            25 => Some(self.uint(2)?),
+            26 => Some(self.uint(4)?),
+            27 => Some(self.uint(8)?),
+            31 if matches!(major, 2..=5) => None,
+            31 if major == 7 => return Err(self.error("unexpected break")),
+            _ => return Err(self.error("invalid additional information")),
+        };
+        Ok((major, arg))
+    }
+
+    /// Reads a break if it is next, ending an indefinite-length item.
+    fn at_break(&mut self) -> Result<bool, Error> {
+        let at_break = self.peek()? == BREAK;
+        if at_break {
+            self.offset += 1;
+        }
+        Ok(at_break)
+    }
+
+    /// Reads the items nested in a sequence, a map or a tag, one level deeper.
+    fn nested<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.depth == RECURSION_LIMIT {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+        value
+    }
+
+    /// The chunks of an indefinite-length string, which all have its major type.
+    fn chunks(&mut self, major: u8) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        while !self.at_break()? {
+            match self.head()? {
+                (m, Some(len)) if m == major => buf.extend_from_slice(self.take(len)?),
+                _ => return Err(self.error("invalid chunk in an indefinite-length string")),
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Reads past the next item without decoding it.
+    fn skip(&mut self) -> Result<(), Error> {
+        let (major, arg) = self.head()?;
+        match (major, arg) {
+            (0 | 1 | 7, _) => {}
+            (2 | 3, Some(len)) => {
+                self.take(len)?;
+            }
+            (2 | 3, None) => {
+                self.chunks(major)?;
+            }
+            (4 | 5, len) => {
+                let items = if major == 5 { 2 } else { 1 };
+                self.nested(|de| match len {
+                    Some(len) => (0..len.saturating_mul(items)).try_for_each(|_| de.skip()),
+                    None => {
+                        while !de.at_break()? {
+                            for _ in 0..items {
+                                de.skip()?;
+                            }
+                        }
+                        Ok(())
+                    }
+                })?;
+            }
+            (6, _) => self.nested(Self::skip)?,
+            _ => unreachable!("the major type has three bits"),
+        }
+        Ok(())
+    }
+
+    fn text(&self, bytes: &[u8]) -> Result<(), Error> {
+        str::from_utf8(bytes)
+            .map(|_| ())
+            .map_err(|_| self.error("invalid UTF-8 in a text string"))
+    }
+}
+
+fn float(info: u8, bits: u64) -> f64 {
+    match info {
+        25 => f16_to_f64(bits as u16),
+        26 => f64::from(f32::from_bits(bits as u32)),
+        27 => f64::from_bits(bits),
+        _ => unreachable!("only floats have a two, four or eight byte argument"),
+    }
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = f64::from(half & 0x3ff);
+    let value = match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mant / 1024.0) * 2f64.powi(i32::from(exp) - 15),
+    };
+    if half & 0x8000 != 0 { -value } else { value }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let info = self.peek()? & 0x1f;
+        let (major, arg) = self.head()?;
+        match (major, arg) {
+            (0, Some(n)) => visitor.visit_u64(n),
+            (1, Some(n)) => match i64::try_from(n) {
+                Ok(n) => visitor.visit_i64(-1 - n),
+                Err(_) => visitor.visit_i128(-1 - i128::from(n)),
+            },
+            (2, Some(len)) => visitor.visit_borrowed_bytes(self.take(len)?),
+            (2, None) => visitor.visit_byte_buf(self.chunks(2)?),
+            (3, Some(len)) => {
+                let bytes = self.take(len)?;
+                self.text(bytes)?;
+                visitor.visit_borrowed_str(str::from_utf8(bytes).unwrap())
+            }
+            (3, None) => {
+                let bytes = self.chunks(3)?;
+                self.text(&bytes)?;
+                visitor.visit_string(String::from_utf8(bytes).unwrap())
+            }
+            (4 | 5, len) => self.nested(|de| {
+                let mut access = Access { de, len };
+                let value = if major == 4 {
+                    visitor.visit_seq(&mut access)?
+                } else {
+                    visitor.visit_map(&mut access)?
+                };
+                access.end()?;
+                Ok(value)
+            }),
+            // tags are ignored, leaving the item they are on
+            (6, _) => self.nested(|de| de.deserialize_any(visitor)),
+            (7, Some(arg)) => match info {
+                20 => visitor.visit_bool(false),
+                21 => visitor.visit_bool(true),
+                22 | 23 => visitor.visit_none(),
+                // other simple values have no serde equivalent, and are read as their number
+                0..=19 => visitor.visit_u8(arg as u8),
+                24 if arg >= 32 => visitor.visit_u8(arg as u8),
+                24 => Err(self.error("invalid simple value")),
+                _ => visitor.visit_f64(float(info, arg)),
+            },
+            _ => unreachable!("only strings, sequences and maps have an indefinite length"),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // null or undefined
+        if matches!(self.peek()?, 0xf6 | 0xf7) {
+            self.offset += 1;
+            return visitor.visit_none();
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name != TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+        let start = self.offset;
+        self.skip()?;
+        visitor.visit_map(RawAccess(Some(&self.input[start..self.offset])))
+    }
+
+    /// A unit variant is a text string, and the others a map with the variant as its only key.
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.peek()? >> 5 == 3 {
+            return visitor.visit_enum(Enum {
+                de: self,
+                unit: true,
+            });
+        }
+        match self.head()? {
+            (5, Some(1)) => visitor.visit_enum(Enum {
+                de: self,
+                unit: false,
+            }),
+            (5, None) => {
+                let value = visitor.visit_enum(Enum {
+                    de: &mut *self,
+                    unit: false,
+                })?;
+                if !self.at_break()? {
+                    return Err(self.error("expected a map with a single entry"));
+                }
+                Ok(value)
+            }
+            _ => Err(self.error("expected a text string or a map with a single entry")),
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.skip()?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+/// The items of a sequence, or the entries of a map.
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    /// The items left, or `None` for an indefinite length until the break.
+    len: Option<u64>,
+}
+
+impl Access<'_, '_> {
+    fn next(&mut self) -> Result<bool, Error> {
+        match &mut self.len {
+            Some(0) => Ok(false),
+            Some(len) => {
+                *len -= 1;
+                Ok(true)
+            }
+            None if self.de.at_break()? => {
+                self.len = Some(0);
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        if self.next()? {
+            return Err(self.de.error("trailing items"));
+        }
+        Ok(())
+    }
+}
+
+impl<'de> SeqAccess<'de> for &mut Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if !self.next()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len.and_then(|len| usize::try_from(len).ok())
+    }
+}
+
+impl<'de> MapAccess<'de> for &mut Access<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !self.next()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len.and_then(|len| usize::try_from(len).ok())
+    }
+}
+
+/// The map a raw value is captured with, like serde_json's own `RawValue`.
+struct RawAccess<'de>(Option<&'de [u8]>);
+
+impl<'de> MapAccess<'de> for RawAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.0.is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(BorrowedStrDeserializer::new(TOKEN))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let raw = self.0.take().expect("the key is read first");
+        seed.deserialize(BorrowedBytesDeserializer::new(raw))
+    }
+}
+
+struct Enum<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    unit: bool,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Enum<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        if self.unit {
+            return Ok(());
+        }
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        if self.unit {
+            return Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a newtype variant",
+            ));
+        }
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.unit {
+            return Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a tuple variant",
+            ));
+        }
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.unit {
+            return Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a struct variant",
+            ));
+        }
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// An error reading CBOR, or from the value it was read into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, marker::PhantomData};
+
+    use proptest::prelude::*;
+    use serde::Deserialize;
+    use serde_json::{Value, json};
+
+    use super::{Deserializer, RawCbor};
+    use crate::{
+        FilterChain, MultiMap, MultiVec, hlist, json_path, map::Map, predicate::NotEq,
+        raw::RawValue2, select::Select,
+    };
+
+    fn cbor(value: Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&value, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn extract_cbor_path<'de, F, T>(cbor: &'de [u8], filter: F) -> Result<T, super::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        let mut de = Deserializer::from_slice(cbor);
+        let value = filter.filter(PhantomData::<T>, &mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    #[test]
+    fn list() {
+        let cbor = cbor(json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [5]
+            },
+            "e": 6,
+        }));
+
+        let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
+        let fields: Value = extract_cbor_path(&cbor, path).unwrap();
+        assert_eq!(fields, json!([3, 5]));
+
+        let path = json_path!(@["b"][@["c"][-1], @["d"][-1]]);
+        let fields: Value = extract_cbor_path(&cbor, path).unwrap();
+        assert_eq!(fields, json!([4, 5]));
+
+        let path = json_path!(@["b"][][0]);
+        let fields: Value = extract_cbor_path(&cbor, path).unwrap();
+        assert_eq!(fields, json!([2, 5]));
+
+        let path = json_path!(@["b"]["c"]..);
+        let fields: Value = extract_cbor_path(&cbor, path).unwrap();
+        assert_eq!(fields, json!([[2, 3, 4], 2, 3, 4]));
+
+        let path = json_path!(@["b"]..[0]);
+        let err = extract_cbor_path::<_, Value>(&cbor, path).unwrap_err();
+        assert!(err.to_string().contains("expected a sequence"), "{err}");
+
+        let fields: Value = extract_cbor_path(&cbor, crate::path!(.b.c[-2:])).unwrap();
+        assert_eq!(fields, json!([3, 4]));
+    }
+
+    #[test]
+    fn map() {
+        let cbor = cbor(json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [{"e": 5}]
+            },
+        }));
+
+        let path = json_path!(@["b"]{
+            "one": @["c"][1],
+            "two": @["d"][0][@["e"], @],
+        });
+        let fields: Value = extract_cbor_path(&cbor, path).unwrap();
+        assert_eq!(fields, json!({"one": 3, "two": [5, {"e": 5}]}));
+    }
+
+    #[test]
+    fn non_string_keys() {
+        use ciborium::Value;
+
+        // {[1]: 2, {"x": null}: 3}
+        let map = Value::Map(vec![
+            (Value::Array(vec![1.into()]), 2.into()),
+            (Value::Map(vec![("x".into(), Value::Null)]), 3.into()),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&map, &mut bytes).unwrap();
+
+        let v: Vec<u32> = extract_cbor_path(&bytes, Map(crate::Final)).unwrap();
+        assert_eq!(v, [2, 3]);
+
+        // the path records such keys as `?`
+        let mut de = Deserializer::from_slice(&bytes);
+        let v: Vec<u32> = crate::track::filter(Map(crate::Final), PhantomData, &mut de).unwrap();
+        assert_eq!(v, [2, 3]);
+        let mut de = Deserializer::from_slice(&bytes);
+        let err =
+            crate::track::filter(Map(crate::Final), PhantomData::<Vec<bool>>, &mut de).unwrap_err();
+        assert_eq!(err.path().to_string(), r#".["?"]"#);
+    }
+
+    #[test]
+    fn multi() {
+        let cbor = cbor(json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [5]
+            },
+            "e": 6,
+        }));
+
+        let fields: Vec<u32> = extract_cbor_path(
+            &cbor,
+            hlist!["b", MultiVec(vec![hlist!["c", 1_usize], hlist!["d", 0]])],
+        )
+        .unwrap();
+        assert_eq!(fields, [3, 5]);
+
+        let fields: HashMap<String, u32> = extract_cbor_path(
+            &cbor,
+            hlist![
+                "b",
+                MultiMap(vec![
+                    ("one".to_string(), hlist!["c", 1_usize]),
+                    ("two".to_string(), hlist!["d", 0])
+                ])
+            ],
+        )
+        .unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields["one"], 3);
+        assert_eq!(fields["two"], 5);
+    }
+
+    #[test]
+    fn raw() {
+        let cbor = cbor(json!({ "events": [
+            {"name": "a", "ph": "X"},
+            {"name": "b", "ph": "B"},
+            {"name": "c", "ph": "E"},
+        ]}));
+
+        // buffered events are re-read from the input, so their names are still borrowed
+        let names: Vec<&str> = extract_cbor_path(
+            &cbor,
+            hlist![
+                "events",
+                Map(hlist![Select(NotEq::new("ph", "X".to_string())), "name"])
+            ],
+        )
+        .unwrap();
+        assert_eq!(names, ["b", "c"]);
+
+        let mut de = Deserializer::from_slice(&cbor);
+        let RawValue2::Cbor(raw) = RawValue2::deserialize(&mut de).unwrap() else {
+            panic!("not raw CBOR");
+        };
+        assert_eq!(raw.as_bytes(), &cbor[..]);
+        let raw = RawCbor::deserialize(&mut Deserializer::from_slice(&cbor)).unwrap();
+        let value: Value = Value::deserialize(raw).unwrap();
+        assert_eq!(value["events"][2]["ph"], "E");
+    }
+
+    #[test]
+    fn simple_values() {
+        assert_eq!(super::from_slice::<u8>(&[0xf0]), Ok(16));
+        assert_eq!(super::from_slice::<u8>(&[0xf8, 0xff]), Ok(255));
+        let err = super::from_slice::<u8>(&[0xf8, 0x10]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid simple value at offset 2");
+        assert_eq!(super::from_slice::<Option<u8>>(&[0xf7]), Ok(None));
+    }
+
+    #[test]
+    fn depth_after_error() {
+        // [[[h'ff' as text]]], which fails three levels down
+        let input = [0x81, 0x81, 0xc1, 0x61, 0xff];
+        let mut de = Deserializer::from_slice(&input);
+        assert!(Value::deserialize(&mut de).is_err());
+        assert_eq!(de.depth, 0);
+        // [[[1, ...]]], cut short
+        let mut de = Deserializer::from_slice(&[0x81, 0x81, 0x82, 0x01]);
+        assert!(de.skip().is_err());
+        assert_eq!(de.depth, 0);
+    }
+
+    /// Any value ciborium encodes, with tags that are read as the item they are on.
+    fn value() -> impl Strategy<Value = ciborium::Value> {
+        use ciborium::Value;
+
+        let leaf = prop_oneof![
+            any::<i64>().prop_map(|n| Value::Integer(n.into())),
+            any::<u64>().prop_map(|n| Value::Integer(n.into())),
+            any::<Vec<u8>>().prop_map(Value::Bytes),
+            any::<String>().prop_map(Value::Text),
+            any::<f64>()
+                .prop_filter("NaN is not equal to itself", |f| !f.is_nan())
+                .prop_map(Value::Float),
+            any::<f32>()
+                .prop_filter("NaN is not equal to itself", |f| !f.is_nan())
+                .prop_map(|f| Value::Float(f.into())),
+            any::<bool>().prop_map(Value::Bool),
+            Just(Value::Null),
+        ];
+        leaf.prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+                prop::collection::vec((inner.clone(), inner.clone()), 0..8).prop_map(Value::Map),
+                (any::<u64>(), inner).prop_map(|(tag, v)| Value::Tag(tag, Box::new(v))),
+            ]
+        })
+    }
+
+    fn untagged(value: ciborium::Value) -> ciborium::Value {
+        use ciborium::Value;
+
+        match value {
+            Value::Tag(_, value) => untagged(*value),
+            Value::Array(items) => Value::Array(items.into_iter().map(untagged).collect()),
+            Value::Map(entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (untagged(k), untagged(v)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn ciborium_round_trip(value in value()) {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&value, &mut bytes).unwrap();
+
+            let decoded: ciborium::Value = super::from_slice(&bytes).unwrap();
+            prop_assert_eq!(decoded, untagged(value));
+
+            let mut de = Deserializer::from_slice(&bytes);
+            let raw = RawCbor::deserialize(&mut de).unwrap();
+            prop_assert_eq!(raw.as_bytes(), &bytes[..]);
+            prop_assert_eq!(de.end(), Ok(()));
+        }
+
+        #[test]
+        fn arbitrary_input(bytes in any::<Vec<u8>>()) {
+            let mut de = Deserializer::from_slice(&bytes);
+            let _ = ciborium::Value::deserialize(&mut de);
+            prop_assert_eq!(de.depth, 0);
+
+            let mut de = Deserializer::from_slice(&bytes);
+            let _ = de.skip();
+            prop_assert_eq!(de.depth, 0);
+        }
+    }
+}
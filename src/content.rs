@@ -0,0 +1,678 @@
+//! A value buffered from any deserializer, to be replayed as many times as needed.
+//!
+//! This is what filters fall back to when the input is not JSON, where they would
+//! otherwise capture a [`RawValue`](serde_json::value::RawValue).
+
+use std::fmt;
+
+use serde::{
+    Serialize, Serializer,
+    de::{
+        self, DeserializeSeed, EnumAccess, IntoDeserializer, Unexpected, VariantAccess,
+        value::{MapDeserializer, SeqDeserializer},
+    },
+    forward_to_deserialize_any,
+    ser::{SerializeMap, SerializeSeq},
+};
+
+/// A buffered value, borrowing strings and bytes from the input when it can.
+///
+/// `&Content` and [`Replay`] are [`Deserializer`](de::Deserializer)s replaying the value. Integers are kept
+/// at their widest, and newtypes are unwrapped. An enum is kept as a map with a single entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content<'de> {
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    U128(u128),
+    I128(i128),
+    F64(f64),
+    Char(char),
+    String(String),
+    Str(&'de str),
+    ByteBuf(Vec<u8>),
+    Bytes(&'de [u8]),
+    None,
+    Some(Box<Content<'de>>),
+    Unit,
+    Seq(Vec<Content<'de>>),
+    Map(Vec<(Content<'de>, Content<'de>)>),
+}
+
+impl<'de> Content<'de> {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Content::String(s) => Some(s),
+            Content::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match *self {
+            Content::Bool(b) => Unexpected::Bool(b),
+            Content::U64(n) => Unexpected::Unsigned(n),
+            Content::I64(n) => Unexpected::Signed(n),
+            Content::U128(_) | Content::I128(_) => Unexpected::Other("128-bit integer"),
+            Content::F64(f) => Unexpected::Float(f),
+            Content::Char(c) => Unexpected::Char(c),
+            Content::String(ref s) => Unexpected::Str(s),
+            Content::Str(s) => Unexpected::Str(s),
+            Content::ByteBuf(ref b) => Unexpected::Bytes(b),
+            Content::Bytes(b) => Unexpected::Bytes(b),
+            Content::None | Content::Some(_) => Unexpected::Option,
+            Content::Unit => Unexpected::Unit,
+            Content::Seq(_) => Unexpected::Seq,
+            Content::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Content<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+pub(crate) struct ContentVisitor;
+
+impl<'de> de::Visitor<'de> for ContentVisitor {
+    type Value = Content<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Content::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Content::I64(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Content::U64(v))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(Content::I128(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(Content::U128(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Content::F64(v))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Self::Value, E> {
+        Ok(Content::Char(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Content::String(v.to_owned()))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(Content::Str(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Content::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Content::ByteBuf(v.to_owned()))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(Content::Bytes(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Content::ByteBuf(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Content::None)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_any(self).map(|v| Content::Some(Box::new(v)))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Content::Unit)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Content::Seq(elements))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Content::Map(entries))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (variant, value) = data.variant::<Content>()?;
+        Ok(Content::Map(vec![(variant, value.newtype_variant()?)]))
+    }
+}
+
+/// Replays the content, as if from a human-readable deserializer like serde_json.
+impl<'de> de::Deserializer<'de> for &Content<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        Replay::new(self, true).deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        Replay::new(self, true).deserialize_option(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Replay::new(self, true).deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Replay::new(self, true).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+/// Replays [`Content`], answering [`is_human_readable`](de::Deserializer::is_human_readable)
+/// like the deserializer it was buffered from.
+#[derive(Clone, Copy, Debug)]
+pub struct Replay<'a, 'de> {
+    content: &'a Content<'de>,
+    human_readable: bool,
+}
+
+impl<'a, 'de> Replay<'a, 'de> {
+    pub fn new(content: &'a Content<'de>, human_readable: bool) -> Self {
+        Self {
+            content,
+            human_readable,
+        }
+    }
+
+    pub fn content(self) -> &'a Content<'de> {
+        self.content
+    }
+
+    /// Nested content, replayed the same way.
+    pub(crate) fn nested(self, content: &'a Content<'de>) -> Self {
+        Self { content, ..self }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Replay<'_, 'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.content {
+            Content::Bool(v) => visitor.visit_bool(*v),
+            Content::U64(v) => visitor.visit_u64(*v),
+            Content::I64(v) => visitor.visit_i64(*v),
+            Content::U128(v) => visitor.visit_u128(*v),
+            Content::I128(v) => visitor.visit_i128(*v),
+            Content::F64(v) => visitor.visit_f64(*v),
+            Content::Char(v) => visitor.visit_char(*v),
+            Content::String(v) => visitor.visit_str(v),
+            Content::Str(v) => visitor.visit_borrowed_str(v),
+            Content::ByteBuf(v) => visitor.visit_bytes(v),
+            Content::Bytes(v) => visitor.visit_borrowed_bytes(v),
+            Content::None => visitor.visit_none(),
+            Content::Some(v) => visitor.visit_some(self.nested(v)),
+            Content::Unit => visitor.visit_unit(),
+            Content::Seq(elements) => {
+                let mut seq = SeqDeserializer::new(elements.iter().map(|v| self.nested(v)));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Content::Map(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(k, v)| (self.nested(k), self.nested(v)));
+                let mut map = MapDeserializer::new(entries);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.content {
+            Content::None | Content::Unit => visitor.visit_none(),
+            Content::Some(v) => visitor.visit_some(self.nested(v)),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.content {
+            Content::Map(entries) if entries.len() == 1 => visitor.visit_enum(Enum {
+                variant: self.nested(&entries[0].0),
+                value: Some(self.nested(&entries[0].1)),
+            }),
+            Content::String(_) | Content::Str(_) => visitor.visit_enum(Enum {
+                variant: self,
+                value: None,
+            }),
+            content => Err(de::Error::invalid_type(content.unexpected(), &"an enum")),
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for Replay<'_, 'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// An enum variant, with its value unless it is a unit variant.
+struct Enum<'a, 'de> {
+    variant: Replay<'a, 'de>,
+    value: Option<Replay<'a, 'de>>,
+}
+
+impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = serde_json::Error;
+    type Variant = Variant<'a, 'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        Ok((seed.deserialize(self.variant)?, Variant(self.value)))
+    }
+}
+
+struct Variant<'a, 'de>(Option<Replay<'a, 'de>>);
+
+impl<'de> VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = serde_json::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.0 {
+            None => Ok(()),
+            Some(value) if *value.content == Content::Unit => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(
+                value.content.unexpected(),
+                &"a unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_seq(value, visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Some(value) => de::Deserializer::deserialize_map(value, visitor),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a struct variant",
+            )),
+        }
+    }
+}
+
+impl Serialize for Content<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Content::Bool(v) => serializer.serialize_bool(*v),
+            Content::U64(v) => serializer.serialize_u64(*v),
+            Content::I64(v) => serializer.serialize_i64(*v),
+            Content::U128(v) => serializer.serialize_u128(*v),
+            Content::I128(v) => serializer.serialize_i128(*v),
+            Content::F64(v) => serializer.serialize_f64(*v),
+            Content::Char(v) => serializer.serialize_char(*v),
+            Content::String(v) => serializer.serialize_str(v),
+            Content::Str(v) => serializer.serialize_str(v),
+            Content::ByteBuf(v) => serializer.serialize_bytes(v),
+            Content::Bytes(v) => serializer.serialize_bytes(v),
+            Content::None => serializer.serialize_none(),
+            Content::Some(v) => serializer.serialize_some(&**v),
+            Content::Unit => serializer.serialize_unit(),
+            Content::Seq(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Content::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        marker::PhantomData,
+    };
+
+    use serde::{Deserialize, de::value::MapDeserializer};
+    use serde_json::json;
+
+    use super::Content;
+    use crate::{FilterChain, hlist};
+
+    #[test]
+    fn replay() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum E {
+            Unit,
+            Newtype(u8),
+            Struct { a: Option<i8> },
+        }
+
+        let input = r#"{"a": [1, -2, 3.5, "s", null], "b": ["Unit", {"Newtype": 4}, {"Struct": {"a": 5}}]}"#;
+        let content: Content = serde_json::from_str(input).unwrap();
+        let Content::Map(entries) = &content else {
+            panic!("{content:?}")
+        };
+        assert_eq!(entries[0].0, Content::Str("a"));
+
+        let value = serde_json::Value::deserialize(&content).unwrap();
+        assert_eq!(
+            value,
+            serde_json::from_str::<serde_json::Value>(input).unwrap()
+        );
+        assert_eq!(serde_json::to_value(&content).unwrap(), value);
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct S<'a> {
+            #[serde(borrow)]
+            a: (u8, i64, f32, &'a str, Option<u8>),
+            b: Vec<E>,
+        }
+        let s = S::deserialize(&content).unwrap();
+        assert_eq!(s.a, (1, -2, 3.5, "s", None));
+        assert_eq!(s.b, [E::Unit, E::Newtype(4), E::Struct { a: Some(5) }]);
+        assert!(u8::deserialize(&Content::I64(-1)).is_err());
+
+        // from a deserializer other than serde_json
+        let map = MapDeserializer::<_, serde_json::Error>::new([(1u8, "x"), (2, "y")].into_iter());
+        let content = Content::deserialize(map).unwrap();
+        let map = BTreeMap::<u64, String>::deserialize(&content).unwrap();
+        assert_eq!(map, BTreeMap::from([(1, "x".into()), (2, "y".into())]));
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            json!({"1": "x", "2": "y"})
+        );
+    }
+
+    #[test]
+    fn other_formats() {
+        use crate::{
+            MultiMap, MultiVec,
+            map::{Map, MapValues},
+            map_select::{MapSelect, MapSelectEntries},
+            predicate::{And, Eq, Has, IsType, JsonType, NotEq},
+            select::Select,
+        };
+
+        // a buffered value is a deserializer that knows nothing about raw JSON values
+        fn from_content<'de, F, T>(content: &Content<'de>, filter: F) -> T
+        where
+            F: FilterChain<'de>,
+            T: serde::Deserialize<'de>,
+        {
+            filter.filter(PhantomData::<T>, content).unwrap()
+        }
+
+        let json = json!({ "events": [
+            {"name": "a", "ph": "X", "args": {}},
+            {"name": "b", "ph": "B"},
+            {"name": "c", "ph": "E", "args": {"x": 1}},
+        ]})
+        .to_string();
+        let content: Content = serde_json::from_str(&json).unwrap();
+
+        let names: Vec<&str> = from_content(
+            &content,
+            hlist![
+                "events",
+                Map(hlist![Select(NotEq::new("ph", "X".to_string())), "name"])
+            ],
+        );
+        assert_eq!(names, ["b", "c"]);
+        let events: Vec<serde_json::Value> = from_content(
+            &content,
+            hlist![
+                "events",
+                MapSelect(And(Has("args"), Eq::new("name", "c".to_string())))
+            ],
+        );
+        assert_eq!(events, [json!({"name": "c", "ph": "E", "args": {"x": 1}})]);
+        let event: HashMap<&str, &str> = from_content(
+            &content,
+            hlist![
+                "events",
+                1,
+                MapSelectEntries(Eq::new("key", "ph".to_string()))
+            ],
+        );
+        assert_eq!(event, HashMap::from([("ph", "B")]));
+        let fields: Vec<&str> = from_content(
+            &content,
+            hlist!["events", 0, MultiVec(vec![hlist!["ph"], hlist!["name"]])],
+        );
+        assert_eq!(fields, ["X", "a"]);
+        let fields: HashMap<String, u32> = from_content(
+            &content,
+            hlist![
+                "events",
+                2,
+                MultiMap(vec![("x".into(), hlist!["args", "x"])])
+            ],
+        );
+        assert_eq!(fields, HashMap::from([("x".to_string(), 1)]));
+        let objects: HashMap<&str, serde_json::Value> = from_content(
+            &content,
+            hlist!["events", 2, MapValues(Select(IsType(JsonType::Object)))],
+        );
+        assert_eq!(objects, HashMap::from([("args", json!({"x": 1}))]));
+
+        let filter = crate::jq::parse(".events | map(select(.ph != \"X\") | .name)").unwrap();
+        let names: Vec<String> = from_content(&content, filter);
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn yaml_and_toml() {
+        use crate::{
+            map::{Map, MapValues, Recurse},
+            optional::Optional,
+            predicate::{Has, NotEq},
+            select::Select,
+        };
+
+        let yaml = "
+services:
+  web:
+    image: nginx
+    ports: [80, 443]
+  db:
+    image: postgres
+    volumes: [data]
+";
+        let yaml = || serde_yaml::Deserializer::from_str(yaml);
+
+        let images: HashMap<String, String> =
+            hlist!["services", MapValues(hlist![Select(Has("ports")), "image"])]
+                .filter(PhantomData, yaml())
+                .unwrap();
+        assert_eq!(
+            images,
+            HashMap::from([("web".to_string(), "nginx".to_string())])
+        );
+        let port: u16 = crate::path!(.services.web.ports[-1])
+            .filter(PhantomData, yaml())
+            .unwrap();
+        assert_eq!(port, 443);
+        let filter = crate::jq::parse("[.services[] | select(.image != \"nginx\") | .volumes[0]]");
+        let volumes: Vec<String> = filter.unwrap().filter(PhantomData, yaml()).unwrap();
+        assert_eq!(volumes, ["data"]);
+        let leaves: Vec<serde_json::Value> = crate::jq::parse("[.. | select(. > 0)]")
+            .unwrap()
+            .filter(PhantomData, yaml())
+            .unwrap();
+        assert_eq!(leaves, [json!(80), json!(443)]);
+        let images: Vec<String> = Recurse::new(Optional("image"))
+            .filter(PhantomData, yaml())
+            .unwrap();
+        assert_eq!(images, ["nginx", "postgres"]);
+
+        let toml = r#"
+[[package]]
+name = "a"
+published = 1979-05-27T07:32:00Z
+
+[[package]]
+name = "b"
+yanked = true
+"#;
+        let toml = || toml::Deserializer::parse(toml).unwrap();
+
+        let names: Vec<String> = hlist![
+            "package",
+            Map(hlist![Select(NotEq::new("name", "b".to_string())), "name"])
+        ]
+        .filter(PhantomData, toml())
+        .unwrap();
+        assert_eq!(names, ["a"]);
+        let yanked: bool = crate::path!(.package[-1].yanked)
+            .filter(PhantomData, toml())
+            .unwrap();
+        assert!(yanked);
+        // datetimes are buffered as the map toml reads them back from
+        let published: Vec<toml::value::Datetime> = hlist![
+            "package",
+            Map(hlist![Select(Has("published")), "published"])
+        ]
+        .filter(PhantomData, toml())
+        .unwrap();
+        assert_eq!(published[0].to_string(), "1979-05-27T07:32:00Z");
+        let published: toml::value::Datetime = crate::jq::parse(".package[0].published")
+            .unwrap()
+            .filter(PhantomData, toml())
+            .unwrap();
+        assert_eq!(published.to_string(), "1979-05-27T07:32:00Z");
+        let names: Vec<String> = crate::jq::parse("[.package[] | select(has(\"yanked\")) | .name]")
+            .unwrap()
+            .filter(PhantomData, toml())
+            .unwrap();
+        assert_eq!(names, ["b"]);
+    }
+}
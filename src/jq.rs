@@ -0,0 +1,1351 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{
+    MultiMap, MultiVec, Slice,
+    json::{JsonField, JsonFieldIndex, JsonLiteral, JsonPath, JsonPredicate},
+    map::Map,
+    map_select::MapSelectEntries,
+    predicate::{
+        And, Contains, EndsWith, Eq, Ge, Gt, Has, HasIndex, Le, Lt, Not, NotEq, Or, StartsWith,
+    },
+    select::Select,
+};
+
+/// Parses a jq filter into a runtime [`JsonPath`].
+///
+/// Only the subset of jq that maps onto the crate's filters is accepted. Anything else
+/// is rejected with [`ParseErrorKind::Unsupported`] rather than being silently misread.
+/// Outputs that jq would stream (`.a, .b`) must be collected with `[...]`.
+pub fn parse(input: &str) -> Result<JsonPath, ParseError> {
+    let (path, stream) = parse_with(input, false)?;
+    if stream {
+        return Err(Parser::new(input, false).error_at(
+            0,
+            ParseErrorKind::Unsupported("multiple outputs, collect them with `[...]`".into()),
+        ));
+    }
+    Ok(path)
+}
+
+/// Parses a jq filter the way the command line runs it.
+///
+/// Unlike [`parse`], a filter with multiple outputs (`.[]`, `.a, .b`) is accepted, and the
+/// returned flag tells that the path yields them as a sequence. A missing key or index,
+/// or one looked up in `null`, is `null` as in jq.
+///
+/// ```
+/// use serde_path::FilterChain;
+///
+/// let (path, stream) = serde_path::jq::parse_program(".[] | .a").unwrap();
+/// assert!(stream);
+/// let mut de = serde_json::Deserializer::from_str(r#"[{"a": 1}, {}, null]"#);
+/// let v = path.filter(std::marker::PhantomData::<Vec<Option<u32>>>, &mut de).unwrap();
+/// assert_eq!(v, [Some(1), None, None]);
+/// ```
+pub fn parse_program(input: &str) -> Result<(JsonPath, bool), ParseError> {
+    parse_with(input, true)
+}
+
+fn parse_with(input: &str, null_on_missing: bool) -> Result<(JsonPath, bool), ParseError> {
+    let mut parser = Parser::new(input, null_on_missing);
+    let (fields, shape) = parser.pipeline(true)?;
+    parser.skip_ws();
+    if parser.pos < input.len() {
+        return Err(parser.leftover("end of input"));
+    }
+    Ok((JsonPath(fields.into_iter()), shape.stream))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Byte offset into the filter text.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 1-based line number.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// 1-based column, counted in characters.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Expected(&'static str),
+    InvalidLiteral(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Expected(expected) => write!(f, "expected {expected}"),
+            ParseErrorKind::InvalidLiteral(err) => write!(f, "invalid literal: {err}"),
+            ParseErrorKind::Unsupported(construct) => {
+                write!(f, "unsupported construct: {construct}")
+            }
+        }
+    }
+}
+
+/// How the output of a (partial) pipeline relates to the [`JsonPath`] model.
+///
+/// A path containing a fan-out (`List`, `Mapped`) applies the remaining fields to each
+/// element and collects the results into a sequence. That matches jq's streams as long as
+/// a stream is never fanned out again, and nothing follows a collected value.
+#[derive(Clone, Copy, Default)]
+struct Shape {
+    /// the output is a stream of values that will be collected into a sequence.
+    stream: bool,
+    /// the output was collected by `[...]`, `{...}` or `map`, no filter may follow.
+    closed: bool,
+    /// the stream comes from `.[]`, so `select` may drop values from it.
+    iterated: bool,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// the filter piped into the condition being parsed, `.a` in `select(.a | .b == 1)`.
+    prefix: Vec<JsonField>,
+    /// keys and indices are `null` when missing, see [`parse_program`].
+    null_on_missing: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, null_on_missing: bool) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            prefix: Vec::new(),
+            null_on_missing,
+        }
+    }
+
+    fn index_field(&self, index: JsonFieldIndex) -> JsonField {
+        if self.null_on_missing {
+            JsonField::IndexOrNull(index)
+        } else {
+            JsonField::Index(index)
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with('#') {
+                break;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &'static str, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.leftover(expected))
+        }
+    }
+
+    fn error_at(&self, offset: usize, kind: ParseErrorKind) -> ParseError {
+        let before = &self.input[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        ParseError {
+            kind,
+            offset,
+            line,
+            column,
+        }
+    }
+
+    fn unsupported(&self, offset: usize, construct: impl Into<String>) -> ParseError {
+        self.error_at(offset, ParseErrorKind::Unsupported(construct.into()))
+    }
+
+    /// Explains why parsing stopped at the current position.
+    fn leftover(&mut self, expected: &'static str) -> ParseError {
+        self.skip_ws();
+        let rest = self.rest();
+        if rest.starts_with(',') {
+            return self.unsupported(self.pos, "`,` outside of `[...]`");
+        }
+        for op in [
+            "!=", "==", "<=", ">=", "//=", "//", "|=", "+=", "-=", "*=", "/=", "%=", "<", ">", "+",
+            "-", "*", "/", "%", "=",
+        ] {
+            if rest.starts_with(op) {
+                return self.unsupported(self.pos, format!("operator `{op}`"));
+            }
+        }
+        let ident = ident_prefix(rest);
+        if matches!(ident, "and" | "or" | "as") {
+            return self.unsupported(self.pos, format!("operator `{ident}`"));
+        }
+        self.error_at(self.pos, ParseErrorKind::Expected(expected))
+    }
+
+    fn append(&self, start: usize, shape: &mut Shape, stage: Shape) -> Result<(), ParseError> {
+        if shape.closed {
+            return Err(self.unsupported(
+                start,
+                "a filter after `map`, `with_entries`, `[...]` or `{...}`",
+            ));
+        }
+        if shape.stream && stage.stream {
+            return Err(self.unsupported(start, "nested output streams"));
+        }
+        shape.stream |= stage.stream;
+        shape.iterated |= stage.iterated;
+        shape.closed = stage.closed;
+        Ok(())
+    }
+
+    /// `stage ('|' stage)*`, where a stage is `term (',' term)*` if `commas` is set.
+    fn pipeline(&mut self, commas: bool) -> Result<(Vec<JsonField>, Shape), ParseError> {
+        let mut fields = Vec::new();
+        let mut shape = Shape::default();
+        loop {
+            self.skip_ws();
+            let start = self.pos;
+            let (stage, stage_shape) = if commas { self.comma()? } else { self.term()? };
+            if shape.stream && !shape.iterated && has_select(&stage) {
+                return Err(self.unsupported(start, "`select` applied to multiple outputs"));
+            }
+            self.append(start, &mut shape, stage_shape)?;
+            fields.extend(stage);
+            if self.at_pipe_into(TESTS) || !self.eat("|") {
+                break;
+            }
+        }
+        Ok((fields, shape))
+    }
+
+    fn comma(&mut self) -> Result<(Vec<JsonField>, Shape), ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        let (first, shape) = self.term()?;
+        if !self.eat(",") {
+            return Ok((first, shape));
+        }
+        if shape.stream {
+            return Err(self.unsupported(start, "nested output streams"));
+        }
+
+        if has_select(&first) {
+            return Err(self.unsupported(start, "`select` applied to multiple outputs"));
+        }
+
+        let mut paths = vec![JsonPath(first.into_iter())];
+        loop {
+            self.skip_ws();
+            let start = self.pos;
+            let (next, shape) = self.term()?;
+            if shape.stream {
+                return Err(self.unsupported(start, "nested output streams"));
+            }
+            if has_select(&next) {
+                return Err(self.unsupported(start, "`select` applied to multiple outputs"));
+            }
+            paths.push(JsonPath(next.into_iter()));
+            if !self.eat(",") {
+                break;
+            }
+        }
+
+        let shape = Shape {
+            stream: true,
+            ..Shape::default()
+        };
+        Ok((vec![JsonField::List(MultiVec(paths))], shape))
+    }
+
+    /// A primary filter followed by any `.key` or `[index]` suffixes.
+    fn term(&mut self) -> Result<(Vec<JsonField>, Shape), ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        let mut fields = Vec::new();
+        let mut shape = Shape::default();
+
+        match self.peek() {
+            Some('.') => {
+                self.pos += 1;
+                if self.rest().starts_with('.') {
+                    self.pos += 1;
+                    shape.stream = true;
+                    shape.iterated = true;
+                    fields.push(JsonField::Recurse);
+                } else if let Some(field) = self.key()? {
+                    self.index(start, &mut shape, &field)?;
+                    fields.push(field);
+                }
+            }
+            Some('[') => {
+                self.pos += 1;
+                if self.eat("]") {
+                    return Err(self.unsupported(start, "empty array construction"));
+                }
+                let (inner, inner_shape) = self.pipeline(true)?;
+                self.expect("]", "`]`")?;
+                if inner_shape.stream {
+                    fields = inner;
+                } else if has_select(&inner) {
+                    return Err(self.unsupported(start, "`select` inside `[...]`, use `map`"));
+                } else {
+                    fields.push(JsonField::List(MultiVec(vec![JsonPath(inner.into_iter())])));
+                }
+                shape.closed = true;
+            }
+            Some('{') => {
+                self.pos += 1;
+                fields.push(JsonField::Map(self.object()?));
+                shape.closed = true;
+            }
+            Some('(') => {
+                self.pos += 1;
+                (fields, shape) = self.pipeline(true)?;
+                self.expect(")", "`)`")?;
+            }
+            Some('$') => return Err(self.unsupported(start, "variables")),
+            Some('"' | '-' | '0'..='9') => {
+                return Err(self.unsupported(start, "literal values outside of comparisons"));
+            }
+            Some(c) if is_ident_start(c) => {
+                let name = ident_prefix(self.rest());
+                self.pos += name.len();
+                match name {
+                    "map" => {
+                        let filter = self.args(|p| {
+                            let start = p.pos;
+                            let (fields, shape) = p.pipeline(true)?;
+                            if shape.stream {
+                                return Err(p.unsupported(start, "multiple outputs in `map`"));
+                            }
+                            Ok(JsonPath(fields.into_iter()))
+                        })?;
+                        fields.push(JsonField::Mapped(Map(filter)));
+                        shape.closed = true;
+                    }
+                    "select" => {
+                        let predicate = self.args(Self::condition)?;
+                        fields.push(JsonField::Select(Select(predicate)));
+                    }
+                    "with_entries" => {
+                        let predicate = self.args(|p| {
+                            p.skip_ws();
+                            let start = p.pos;
+                            if !p.eat_keyword("select") {
+                                return Err(p.unsupported(start, "`with_entries` without `select`"));
+                            }
+                            p.args(Self::condition)
+                        })?;
+                        fields.push(JsonField::SelectEntries(MapSelectEntries(predicate)));
+                        shape.closed = true;
+                    }
+                    "true" | "false" | "null" => {
+                        return Err(
+                            self.unsupported(start, "literal values outside of comparisons")
+                        );
+                    }
+                    _ => return Err(self.unsupported(start, format!("function `{name}`"))),
+                }
+            }
+            _ => return Err(self.error_at(start, ParseErrorKind::Expected("a filter"))),
+        }
+
+        self.suffixes(&mut fields, &mut shape)?;
+        Ok((fields, shape))
+    }
+
+    fn suffixes(
+        &mut self,
+        fields: &mut Vec<JsonField>,
+        shape: &mut Shape,
+    ) -> Result<(), ParseError> {
+        let primary_stream = shape.stream;
+        // where the fields of the last primary or suffix start, for `?`.
+        let mut last = 0;
+        loop {
+            self.skip_ws();
+            let start = self.pos;
+            let rest = self.rest();
+            if rest.starts_with('?') {
+                let inner = fields.split_off(last);
+                let stream = if last == 0 {
+                    primary_stream
+                } else {
+                    inner
+                        .iter()
+                        .any(|f| matches!(f, JsonField::Each | JsonField::Recurse))
+                };
+                if stream {
+                    return Err(self.unsupported(start, "`?` applied to multiple outputs"));
+                }
+                if shape.closed {
+                    return Err(self.unsupported(start, "`?` applied to a collected value"));
+                }
+                self.pos += 1;
+                fields.push(JsonField::Optional(JsonPath(inner.into_iter())));
+                continue;
+            }
+            let dotted = rest.starts_with('.')
+                && rest[1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c == '"' || c == '[' || is_ident_start(c));
+            if !dotted && !rest.starts_with('[') {
+                return Ok(());
+            }
+            if shape.closed {
+                return Err(self.unsupported(start, "indexing the output of a collecting filter"));
+            }
+            if dotted {
+                self.pos += 1;
+            }
+            if let Some(field) = self.key()? {
+                self.index(start, shape, &field)?;
+                last = fields.len();
+                fields.push(field);
+            }
+        }
+    }
+
+    fn index(&self, start: usize, shape: &mut Shape, field: &JsonField) -> Result<(), ParseError> {
+        match field {
+            // a slice collects the selected elements.
+            JsonField::Slice(_) => shape.closed = true,
+            JsonField::Each if shape.stream => {
+                return Err(self.unsupported(start, "nested output streams"));
+            }
+            JsonField::Each => {
+                shape.stream = true;
+                shape.iterated = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The part of a path after the `.`: `foo`, `"foo"` or `[...]`.
+    fn key(&mut self) -> Result<Option<JsonField>, ParseError> {
+        match self.peek() {
+            Some(c) if is_ident_start(c) => {
+                let name = ident_prefix(self.rest());
+                self.pos += name.len();
+                Ok(Some(self.index_field(JsonFieldIndex::Map(name.to_owned()))))
+            }
+            Some('"') => match self.literal()? {
+                Value::String(key) => Ok(Some(self.index_field(JsonFieldIndex::Map(key)))),
+                _ => unreachable!("a literal starting with `\"` is a string"),
+            },
+            Some('[') => self.bracket().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// `[]`, `[0]`, `["key"]` or `[start:end]`.
+    fn bracket(&mut self) -> Result<JsonField, ParseError> {
+        self.pos += 1;
+        if self.eat("]") {
+            return Ok(JsonField::Each);
+        }
+
+        self.skip_ws();
+        let index_start = self.pos;
+        let index = match self.peek() {
+            Some('"' | '-' | '0'..='9') => Some(self.literal()?),
+            Some(':') => None,
+            _ => return Err(self.unsupported(index_start, "computed indices")),
+        };
+        if self.eat(":") {
+            let start = index
+                .map(|index| self.slice_bound(index_start, index))
+                .transpose()?;
+            self.skip_ws();
+            let end_start = self.pos;
+            let end = match self.peek() {
+                Some('-' | '0'..='9') => Some(self.literal()?),
+                _ => None,
+            };
+            let end = end
+                .map(|end| self.slice_bound(end_start, end))
+                .transpose()?;
+            self.expect("]", "`]`")?;
+            let slice = Slice {
+                start,
+                end,
+                ..Slice::default()
+            };
+            return Ok(JsonField::Slice(slice));
+        }
+        self.expect("]", "`]`")?;
+
+        let index = match index {
+            Some(Value::String(key)) => JsonFieldIndex::Map(key),
+            Some(Value::Number(n)) if n.is_u64() => match usize::try_from(n.as_u64().unwrap()) {
+                Ok(index) => JsonFieldIndex::List(index),
+                Err(_) => return Err(self.out_of_range(index_start)),
+            },
+            Some(Value::Number(n)) if n.is_i64() => {
+                JsonFieldIndex::from_end(n.as_i64().unwrap().unsigned_abs() as usize)
+            }
+            // `-0`, which jq reads as `0`
+            Some(Value::Number(n)) if n.as_f64() == Some(0.0) => JsonFieldIndex::List(0),
+            _ => {
+                return Err(self.error_at(
+                    index_start,
+                    ParseErrorKind::Expected("an array index or object key"),
+                ));
+            }
+        };
+        Ok(self.index_field(index))
+    }
+
+    fn slice_bound(&self, offset: usize, bound: Value) -> Result<isize, ParseError> {
+        match bound {
+            Value::Number(n) if n.is_i64() => {
+                isize::try_from(n.as_i64().unwrap()).map_err(|_| self.out_of_range(offset))
+            }
+            Value::Number(n) if n.is_u64() => Err(self.out_of_range(offset)),
+            _ => Err(self.error_at(offset, ParseErrorKind::Expected("an integer"))),
+        }
+    }
+
+    fn out_of_range(&self, offset: usize) -> ParseError {
+        self.error_at(
+            offset,
+            ParseErrorKind::InvalidLiteral("index out of range".into()),
+        )
+    }
+
+    /// `(...)` around the single argument of a builtin.
+    fn args<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.expect("(", "`(`")?;
+        let value = f(self)?;
+        if self.eat(";") {
+            return Err(self.unsupported(self.pos - 1, "multiple arguments"));
+        }
+        self.expect(")", "`)`")?;
+        Ok(value)
+    }
+
+    /// The entries of `{...}`, after the opening brace.
+    fn object(&mut self) -> Result<MultiMap<JsonPath>, ParseError> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(MultiMap(entries));
+        }
+        loop {
+            self.skip_ws();
+            let start = self.pos;
+            let key = match self.peek() {
+                Some('"') => match self.literal()? {
+                    Value::String(key) => key,
+                    _ => unreachable!("a literal starting with `\"` is a string"),
+                },
+                Some(c) if is_ident_start(c) => {
+                    let name = ident_prefix(self.rest());
+                    self.pos += name.len();
+                    name.to_owned()
+                }
+                Some('$') => return Err(self.unsupported(start, "variables")),
+                Some('(') => return Err(self.unsupported(start, "computed keys")),
+                _ => return Err(self.error_at(start, ParseErrorKind::Expected("an object key"))),
+            };
+
+            let value = if self.eat(":") {
+                self.skip_ws();
+                let start = self.pos;
+                let (fields, shape) = self.pipeline(false)?;
+                if shape.stream {
+                    return Err(self.unsupported(start, "multiple outputs in an object value"));
+                }
+                JsonPath(fields.into_iter())
+            } else {
+                // `{a}` is shorthand for `{a: .a}`
+                JsonPath(vec![self.index_field(JsonFieldIndex::Map(key.clone()))].into_iter())
+            };
+            entries.push((key, value));
+
+            if self.eat("}") {
+                return Ok(MultiMap(entries));
+            }
+            self.expect(",", "`,` or `}`")?;
+        }
+    }
+
+    /// The argument of `select`: comparisons combined with `and`, `or`, `| not` and parentheses.
+    ///
+    /// `|` binds loosest, like in jq: `.a | .b == 1 or .c == 2` tests `.a.b` and `.a.c`.
+    fn condition(&mut self) -> Result<JsonPredicate, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        if let Ok(Operand::Filter(filter)) = self.operand()
+            && !self.at_pipe_into(&["not"])
+            && self.eat("|")
+        {
+            let prefix = std::mem::replace(&mut self.prefix, filter.0.collect());
+            let predicate = self.condition();
+            self.prefix = prefix;
+            return predicate;
+        }
+        self.pos = start;
+
+        let mut lhs = self.conjunction()?;
+        while self.eat_keyword("or") {
+            let rhs = self.conjunction()?;
+            lhs = JsonPredicate::Or(Or(Box::new(lhs), Box::new(rhs)));
+        }
+        while self.at_pipe_into(&["not"]) {
+            self.eat("|");
+            self.eat_keyword("not");
+            lhs = JsonPredicate::Not(Not(Box::new(lhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn conjunction(&mut self) -> Result<JsonPredicate, ParseError> {
+        let mut lhs = self.comparison()?;
+        while self.eat_keyword("and") {
+            let rhs = self.comparison()?;
+            lhs = JsonPredicate::And(And(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    /// `(condition)`, or a comparison if the parentheses group a filter instead.
+    fn comparison(&mut self) -> Result<JsonPredicate, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.eat("(") {
+            let grouped = self
+                .condition()
+                .and_then(|p| self.expect(")", "`)`").map(|()| p));
+            match grouped {
+                Ok(predicate) => return Ok(predicate),
+                Err(err) => {
+                    self.pos = start;
+                    return self.predicate().map_err(|_| err);
+                }
+            }
+        }
+        match ident_prefix(self.rest()) {
+            "has" if !self.prefix.is_empty() => {
+                return Err(self.unsupported(start, "`has` after `|`"));
+            }
+            "has" => {
+                self.pos += "has".len();
+                return self.args(Self::has);
+            }
+            name if STRING_TESTS.contains(&name) => {
+                return self.string_test(JsonPath(self.prefix.clone().into_iter()));
+            }
+            _ => {}
+        }
+        self.predicate()
+    }
+
+    /// The argument of `has`: a key or an index.
+    fn has(&mut self) -> Result<JsonPredicate, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        match self.literal()? {
+            Value::String(key) => Ok(JsonPredicate::Has(Has(key))),
+            Value::Number(n) => match n.as_u64().and_then(|n| usize::try_from(n).ok()) {
+                Some(n) => Ok(JsonPredicate::HasIndex(HasIndex(n))),
+                None => Err(self.error_at(
+                    start,
+                    ParseErrorKind::InvalidLiteral("index out of range".into()),
+                )),
+            },
+            _ => Err(self.error_at(start, ParseErrorKind::Expected("a key or an index"))),
+        }
+    }
+
+    /// A string test on `filter`: `startswith("a")`.
+    fn string_test(&mut self, filter: JsonPath) -> Result<JsonPredicate, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        let name = ident_prefix(self.rest());
+        if name == "test" && !cfg!(feature = "regex") {
+            return Err(self.unsupported(start, "regular expressions without the `regex` feature"));
+        }
+        self.pos += name.len();
+        let pattern = self.args(|p| {
+            p.skip_ws();
+            let start = p.pos;
+            match p.literal()? {
+                Value::String(pattern) => Ok(pattern),
+                _ => Err(p.error_at(start, ParseErrorKind::Expected("a string"))),
+            }
+        })?;
+        Ok(match name {
+            "contains" => JsonPredicate::Contains(Contains::new(filter, pattern)),
+            "startswith" => JsonPredicate::StartsWith(StartsWith::new(filter, pattern)),
+            "endswith" => JsonPredicate::EndsWith(EndsWith::new(filter, pattern)),
+            #[cfg(feature = "regex")]
+            _ => {
+                let regex = regex::Regex::new(&pattern).map_err(|err| {
+                    self.error_at(start, ParseErrorKind::InvalidLiteral(err.to_string()))
+                })?;
+                JsonPredicate::Matches(crate::predicate::Matches::new(filter, regex))
+            }
+            #[cfg(not(feature = "regex"))]
+            _ => unreachable!("`test` is rejected above"),
+        })
+    }
+
+    /// Whether `| name` is next, for one of the names.
+    fn at_pipe_into(&self, names: &[&str]) -> bool {
+        let rest = self.rest().trim_start();
+        rest.strip_prefix('|')
+            .is_some_and(|rest| names.contains(&ident_prefix(rest.trim_start())))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_ws();
+        if ident_prefix(self.rest()) == keyword {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// `filter op literal` or `literal op filter`.
+    fn predicate(&mut self) -> Result<JsonPredicate, ParseError> {
+        let lhs = self.operand()?;
+        self.skip_ws();
+        let op_start = self.pos;
+        let Some(op) = ["==", "!=", "<=", ">=", "<", ">"]
+            .into_iter()
+            .find(|op| self.eat(op))
+        else {
+            let err = self.leftover("a comparison");
+            return Err(match err.kind {
+                ParseErrorKind::Expected(_) => {
+                    self.unsupported(op_start, "`select` without a comparison")
+                }
+                _ => err,
+            });
+        };
+        let rhs = self.operand()?;
+
+        let (filter, value, op) = match (lhs, rhs) {
+            (Operand::Filter(filter), Operand::Literal(value)) => (filter, value, op),
+            // `1 < .a` is `.a > 1`
+            (Operand::Literal(value), Operand::Filter(filter)) => {
+                let op = match op {
+                    "<" => ">",
+                    ">" => "<",
+                    "<=" => ">=",
+                    ">=" => "<=",
+                    op => op,
+                };
+                (filter, value, op)
+            }
+            (Operand::Filter(_), Operand::Filter(_)) => {
+                return Err(self.unsupported(op_start, "comparison between two filters"));
+            }
+            (Operand::Literal(_), Operand::Literal(_)) => {
+                return Err(self.unsupported(op_start, "comparison between two literals"));
+            }
+        };
+        let value = JsonLiteral(value);
+        Ok(match op {
+            "==" => JsonPredicate::Eq(Eq::new(filter, value)),
+            "!=" => JsonPredicate::NotEq(NotEq::new(filter, value)),
+            "<" => JsonPredicate::Lt(Lt::new(filter, value)),
+            "<=" => JsonPredicate::Le(Le::new(filter, value)),
+            ">" => JsonPredicate::Gt(Gt::new(filter, value)),
+            _ => JsonPredicate::Ge(Ge::new(filter, value)),
+        })
+    }
+
+    fn operand(&mut self) -> Result<Operand, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        match self.peek() {
+            Some('"' | '-' | '0'..='9') => return self.literal().map(Operand::Literal),
+            Some('t' | 'f' | 'n')
+                if matches!(ident_prefix(self.rest()), "true" | "false" | "null") =>
+            {
+                return self.literal().map(Operand::Literal);
+            }
+            _ => {}
+        }
+
+        let (fields, shape) = self.term()?;
+        if shape.stream {
+            return Err(self.unsupported(start, "multiple outputs in a comparison"));
+        }
+        let fields = self.prefix.iter().cloned().chain(fields);
+        Ok(Operand::Filter(JsonPath(
+            fields.collect::<Vec<_>>().into_iter(),
+        )))
+    }
+
+    /// A JSON scalar: a string, a number, `true`, `false` or `null`.
+    fn literal(&mut self) -> Result<Value, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        let rest = self.rest();
+        let len = match rest.chars().next() {
+            Some('"') => {
+                let mut escaped = false;
+                let mut interpolated = false;
+                let end = rest[1..].char_indices().find(|&(_, c)| {
+                    interpolated |= escaped && c == '(';
+                    let end = !escaped && c == '"';
+                    escaped = !escaped && c == '\\';
+                    end
+                });
+                if interpolated {
+                    return Err(self.unsupported(start, "string interpolation"));
+                }
+                match end {
+                    Some((i, _)) => i + 2,
+                    None => {
+                        return Err(self.error_at(
+                            start,
+                            ParseErrorKind::InvalidLiteral("unterminated string".into()),
+                        ));
+                    }
+                }
+            }
+            Some('-' | '0'..='9') => rest
+                .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                .unwrap_or(rest.len()),
+            _ => ident_prefix(rest).len(),
+        };
+
+        let text = &rest[..len];
+        let value = serde_json::from_str(text)
+            .map_err(|err| self.error_at(start, ParseErrorKind::InvalidLiteral(err.to_string())))?;
+        self.pos += len;
+        Ok(value)
+    }
+}
+
+/// Builtins that test a string, `.name | startswith("a")`.
+const STRING_TESTS: &[&str] = &["contains", "startswith", "endswith", "test"];
+
+/// Where a `select` pipeline ends and its test begins.
+const TESTS: &[&str] = &["not", "contains", "startswith", "endswith", "test"];
+
+enum Operand {
+    Filter(JsonPath),
+    Literal(Value),
+}
+
+/// A `select` that rejects its input makes the whole path fail. Only `map` turns that
+/// into skipping an element, so it cannot appear directly inside a `List`.
+fn has_select(fields: &[JsonField]) -> bool {
+    fields.iter().any(|f| matches!(f, JsonField::Select(_)))
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn ident_prefix(s: &str) -> &str {
+    let end = s
+        .char_indices()
+        .find(|&(i, c)| !(is_ident_start(c) || (i > 0 && c.is_ascii_digit())))
+        .map_or(s.len(), |(i, _)| i);
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::{Value, json, value::RawValue};
+
+    use crate::{
+        FilterChain, hlist,
+        jq::{ParseErrorKind, parse, parse_program},
+        map::Map,
+        predicate::NotEq,
+        select::Select,
+    };
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    fn query(json: &Value, filter: &str) -> Value {
+        let json = json.to_string();
+        extract_json_path(&json, parse(filter).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        let json = json!({ "a": 1, "b": {"c": [2, 3, 4], "d e": [5]} });
+
+        assert_eq!(query(&json, "."), json);
+        assert_eq!(query(&json, ".b.c[1]"), json!(3));
+        assert_eq!(query(&json, ".b | .c | .[2]"), json!(4));
+        assert_eq!(query(&json, r#".b."d e"[0]"#), json!(5));
+        assert_eq!(query(&json, r#".["b"]["c"].[0]"#), json!(2));
+        assert_eq!(query(&json, ".b.c[-1]"), json!(4));
+        assert_eq!(query(&json, ".b.c[-3]"), json!(2));
+        assert_eq!(query(&json, ".b.c[-0]"), json!(2));
+        assert_eq!(query(&json, ".b.c[1:]"), json!([3, 4]));
+        assert_eq!(query(&json, ".b.c[:-1]"), json!([2, 3]));
+        assert_eq!(query(&json, ".b | .c[-2:10]"), json!([3, 4]));
+    }
+
+    #[test]
+    fn construction() {
+        let json = json!({ "a": 1, "b": {"c": [2, 3, 4], "d": [5]} });
+
+        assert_eq!(query(&json, ".b | [.c[1], .d[0]]"), json!([3, 5]));
+        assert_eq!(query(&json, "[.a]"), json!([1]));
+        assert_eq!(query(&json, "[.b | .c[0], .d[0]]"), json!([2, 5]));
+        assert_eq!(query(&json, "[.b.c, .b.d | .[0]]"), json!([2, 5]));
+        assert_eq!(
+            query(&json, r#".b | {one: .c[1], "two": .d | .[0], d}"#),
+            json!({"one": 3, "two": 5, "d": [5]})
+        );
+    }
+
+    #[test]
+    fn map_select() {
+        let json = json!({
+            "traceEvents": [
+                {"ph": "X", "name": "a"},
+                {"ph": "M", "name": "b"},
+                {"ph": "B", "name": "c"},
+            ]
+        })
+        .to_string();
+
+        let expected: Value = extract_json_path(
+            &json,
+            hlist![
+                "traceEvents",
+                Map(Select(NotEq::new("ph", "X".to_string())))
+            ],
+        )
+        .unwrap();
+
+        let filter = parse(r#".traceEvents | map(select(.ph != "X"))"#).unwrap();
+        let actual: Value = extract_json_path(&json, filter).unwrap();
+        assert_eq!(actual, expected);
+
+        let filter = parse(r#".traceEvents | map(select("X" != .ph) | .name)"#).unwrap();
+        let actual: Value = extract_json_path(&json, filter).unwrap();
+        assert_eq!(actual, json!(["b", "c"]));
+
+        let filter = parse(r#"[.traceEvents[] | select(.ph != "X")]"#).unwrap();
+        let actual: Value = extract_json_path(&json, filter).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn program() {
+        let program = |filter: &str, json: &str| -> Result<Value, _> {
+            let (path, _) = parse_program(filter).unwrap();
+            extract_json_path(json, path)
+        };
+        assert_eq!(program(".a.b[0]", "{}").unwrap(), json!(null));
+        assert_eq!(program(".a[-1]", r#"{"a": []}"#).unwrap(), json!(null));
+        assert_eq!(
+            program("{b: .a.b}", r#"{"a": null}"#).unwrap(),
+            json!({"b": null})
+        );
+        assert_eq!(
+            program(r#"map(select(.a == null) | .b)"#, r#"[{"b": 1}, {"a": 2}]"#).unwrap(),
+            json!([1])
+        );
+        // indexing a value of another type still fails
+        assert!(program(".a[0]", r#"{"a": {}}"#).is_err());
+        assert!(program(".a", "[1]").is_err());
+        let (path, _) = parse_program(".a.b[0]").unwrap();
+        let yaml = serde_yaml::Deserializer::from_str("a: {c: 1}");
+        let v: Value = path.filter(PhantomData, yaml).unwrap();
+        assert_eq!(v, json!(null));
+
+        let (_, stream) = parse_program(".a, .b").unwrap();
+        assert!(stream);
+        let (_, stream) = parse_program("[.[]]").unwrap();
+        assert!(!stream);
+        assert!(parse(".a, .b").is_err());
+    }
+
+    #[test]
+    fn iterate() {
+        let json = json!({ "a": {"x": {"n": 1}, "y": {"n": 2}}, "b": [[1, 2], [3]] });
+
+        assert_eq!(query(&json, "[.a[]]"), json!([{"n": 1}, {"n": 2}]));
+        assert_eq!(query(&json, "[.a[].n]"), json!([1, 2]));
+        assert_eq!(query(&json, "[.b[] | .[0]]"), json!([1, 3]));
+        assert_eq!(query(&json, "[.a[] | select(.n != 1) | .n]"), json!([2]));
+    }
+
+    #[test]
+    fn optional() {
+        let json = json!({ "a": [{"b": 1}, {"c": 2}, 3, {"b": 4}], "d": "x" });
+
+        assert_eq!(query(&json, ".x?"), Value::Null);
+        assert_eq!(query(&json, ".d.e?"), Value::Null);
+        assert_eq!(query(&json, ".d?"), json!("x"));
+        assert_eq!(query(&json, "[.a[] | .b?]"), json!([1, 4]));
+        assert_eq!(query(&json, "[.a[].b?]"), json!([1, 4]));
+        assert_eq!(query(&json, "[.a[0].b?, .x?, .d]"), json!([1, "x"]));
+        assert_eq!(query(&json, "{b: .a[1].b?}"), json!({"b": null}));
+    }
+
+    #[test]
+    fn recurse() {
+        let json = json!({ "a": {"n": 1, "b": [{"n": 2}]}, "n": 3 });
+
+        assert_eq!(query(&json, "[.. | .n?]"), json!([3, 1, 2]));
+        assert_eq!(
+            query(&json, "[.a.b | ..]"),
+            json!([[{"n": 2}], {"n": 2}, 2])
+        );
+        assert_eq!(
+            query(&json, "[.a | .. | select(.n? != 1)]"),
+            json!([[{"n": 2}], {"n": 2}, 2, 1])
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let json = json!({ "e": [
+            {"name": "a", "dur": 999},
+            {"name": "b", "dur": 1000.5},
+            {"name": "c", "dur": 1000.0},
+            {"name": "d", "dur": "x"},
+        ]});
+
+        assert_eq!(
+            query(&json, "[.e[] | select(.dur > 1000) | .name]"),
+            json!(["b"])
+        );
+        assert_eq!(
+            query(&json, "[.e[] | select(1000 <= .dur) | .name]"),
+            json!(["b", "c"])
+        );
+        assert_eq!(
+            query(&json, "[.e[] | select(.dur == 1000) | .name]"),
+            json!(["c"])
+        );
+        assert_eq!(
+            query(&json, "[.e[] | select(.dur != 1000) | .name]"),
+            json!(["a", "b", "d"])
+        );
+        assert_eq!(
+            query(&json, "[.e[] | select(.dur < 1000) | .name]"),
+            json!(["a"])
+        );
+        assert_eq!(
+            query(&json, r#"[.e[] | select(.name >= "c") | .name]"#),
+            json!(["c", "d"])
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let json = json!({ "e": [
+            {"name": "a", "ph": "X", "dur": 2000},
+            {"name": "b", "ph": "B", "dur": 2000},
+            {"name": "c", "ph": "B", "dur": 10},
+            {"name": "d", "ph": "E", "dur": {"us": 5}},
+        ]});
+        let names = |filter: &str| query(&json, &format!("[.e[] | select({filter}) | .name]"));
+
+        assert_eq!(names(r#".ph != "X" and .dur > 1000"#), json!(["b"]));
+        assert_eq!(names(r#".ph == "X" or .name == "c""#), json!(["a", "c"]));
+        assert_eq!(
+            names(r#".ph == "E" or .ph == "B" and .dur < 100"#),
+            json!(["c", "d"])
+        );
+        assert_eq!(
+            names(r#"(.ph == "E" or .ph == "B") and .name != "d""#),
+            json!(["b", "c"])
+        );
+        assert_eq!(names(r#".ph == "B" | not"#), json!(["a", "d"]));
+        assert_eq!(
+            names(r#".ph == "B" and (.name == "c" | not)"#),
+            json!(["b"])
+        );
+        // `.dur.us` fails on numbers, so it only runs on "d".
+        assert_eq!(names(r#".name == "d" and .dur.us == 5"#), json!(["d"]));
+        assert_eq!(
+            names(r#".name != "d" or .dur.us == 5"#),
+            json!(["a", "b", "c", "d"])
+        );
+        assert_eq!(names(r#"has("ph") and (has("dur") | not)"#), json!([]));
+        assert_eq!(names(r#"has("dur") and .name > "c""#), json!(["d"]));
+        assert_eq!(
+            query(&json!([[1], [1, 2], {"1": 2}]), "[.[] | select(has(1))]"),
+            json!([[1, 2]])
+        );
+        let filter = parse(r#"[.e[] | select(.dur.us == 5 and .name == "d")]"#).unwrap();
+        assert!(extract_json_path::<_, Value>(&json.to_string(), filter).is_err());
+    }
+
+    #[test]
+    fn strings() {
+        let json = json!({ "e": [
+            {"name": "OptimizeModule"},
+            {"name": "RunPass"},
+            {"name": "RunLoopPass"},
+        ]});
+        let names = |filter: &str| query(&json, &format!("[.e[] | select({filter}) | .name]"));
+
+        assert_eq!(
+            names(r#".name | startswith("Run")"#),
+            json!(["RunPass", "RunLoopPass"])
+        );
+        assert_eq!(
+            names(r#"(.name | endswith("Module")) or (.name | contains("Loop"))"#),
+            json!(["OptimizeModule", "RunLoopPass"])
+        );
+        // `|` binds loosest, so the whole condition applies to `.name`
+        assert_eq!(
+            names(r#".name | endswith("Module") or contains("Loop")"#),
+            json!(["OptimizeModule", "RunLoopPass"])
+        );
+        assert_eq!(
+            names(r#".name | startswith("Run") | not"#),
+            json!(["OptimizeModule"])
+        );
+        assert_eq!(
+            query(&json, r#"[.e[].name | select(contains("Pass") | not)]"#),
+            json!(["OptimizeModule"])
+        );
+
+        #[cfg(feature = "regex")]
+        assert_eq!(
+            names(r#".name | test("^Run.*Pass$")"#),
+            json!(["RunPass", "RunLoopPass"])
+        );
+        #[cfg(not(feature = "regex"))]
+        assert!(matches!(
+            parse(r#"[.e[] | select(.name | test("^Run"))]"#)
+                .unwrap_err()
+                .kind(),
+            ParseErrorKind::Unsupported(_)
+        ));
+    }
+
+    #[test]
+    fn with_entries() {
+        let json = json!({"x-a": 1, "y": 2, "x-b": 3});
+
+        assert_eq!(
+            query(&json, r#"with_entries(select(.key | startswith("x-")))"#),
+            json!({"x-a": 1, "x-b": 3})
+        );
+        assert_eq!(
+            query(
+                &json,
+                r#"with_entries(select(.value >= 2 and (.key | contains("-"))))"#
+            ),
+            json!({"x-b": 3})
+        );
+        assert!(matches!(
+            parse("with_entries(select(.value > 1)) | .y")
+                .unwrap_err()
+                .kind(),
+            ParseErrorKind::Unsupported(_)
+        ));
+    }
+
+    #[test]
+    fn select_then_path() {
+        let json = json!({ "b": {"c": [2, 3, 4], "d": [5]} });
+
+        assert_eq!(query(&json, ".b | select(.d[0] != 4) | .c[2]"), json!(4));
+    }
+
+    #[test]
+    fn errors() {
+        fn unsupported(filter: &str) -> (String, usize) {
+            let err = parse(filter).unwrap_err();
+            match err.kind() {
+                ParseErrorKind::Unsupported(construct) => (construct.clone(), err.column()),
+                kind => panic!("{filter}: expected unsupported, got {kind:?}"),
+            }
+        }
+
+        assert_eq!(unsupported(".a | ..").1, 1);
+        assert_eq!(unsupported("[.[] | ..]").1, 8);
+        assert_eq!(
+            unsupported(".a[]"),
+            ("multiple outputs, collect them with `[...]`".to_string(), 1)
+        );
+        assert_eq!(unsupported("[.a[][]]").1, 6);
+        assert_eq!(unsupported("[.a, .b | .[]]").1, 11);
+        assert_eq!(unsupported(".a[1:2][0]").1, 8);
+        assert_eq!(unsupported(".a[1:2] | .b").1, 11);
+        assert_eq!(unsupported("[.a[]?]").1, 6);
+        assert_eq!(unsupported("[(.a, .b)?]").1, 10);
+        assert_eq!(unsupported(".a == 1"), ("operator `==`".to_string(), 4));
+        assert_eq!(unsupported("select(.a)").0, "`select` without a comparison");
+        assert_eq!(unsupported("length").0, "function `length`");
+        assert_eq!(
+            unsupported(".a, .b").0,
+            "multiple outputs, collect them with `[...]`"
+        );
+        assert_eq!(unsupported("select(.a, .b)").0, "`,` outside of `[...]`");
+        assert_eq!(unsupported("map(.a) | .b").1, 11);
+        assert_eq!(unsupported("[.a, .b][0]").1, 9);
+        assert_eq!(unsupported("[.a, .b | select(.c != 1)]").1, 11);
+        assert_eq!(
+            unsupported(r#"select(.a != "x\(.b)")"#),
+            ("string interpolation".to_string(), 14)
+        );
+        // an escaped backslash followed by `(`
+        let json = json!([r"a\(b", "a(b"]);
+        assert_eq!(
+            query(&json, r#"[.[] | select(. == "a\\(b")]"#),
+            json!([r"a\(b"])
+        );
+
+        let err = parse(".a |\n  .b | ]").unwrap_err();
+        assert_eq!(err.kind(), &ParseErrorKind::Expected("a filter"));
+        assert_eq!((err.line(), err.column(), err.offset()), (2, 8, 12));
+        assert_eq!(err.to_string(), "expected a filter at line 2, column 8");
+
+        let err = parse(r#"select(.a != "b)"#).unwrap_err();
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidLiteral(_)));
+        assert_eq!(err.column(), 14);
+
+        let err = parse("{a: .b").unwrap_err();
+        assert_eq!(err.kind(), &ParseErrorKind::Expected("`,` or `}`"));
+    }
+
+    #[test]
+    fn path_macro() {
+        use crate::{Final, FromEnd, Slice};
+
+        let filter: (&str, (&str, (usize, Final))) = crate::path!(.b.c[1]);
+        let json = json!({ "a": 1, "b": {"c": [2, 3, 4]}, "d": 5}).to_string();
+        let field: &RawValue = extract_json_path(&json, filter).unwrap();
+        assert_eq!(field.get(), "3");
+
+        type NotX = Select<NotEq<(&'static str, Final), String>>;
+        type TraceEvents = (&'static str, (Map<(NotX, Final)>, Final));
+        let filter: TraceEvents = crate::path!(.traceEvents[] | select(.ph != "X"));
+        let json = json!({ "traceEvents": [
+            {"ph": "X", "name": "a"},
+            {"ph": "B", "name": "b"},
+            {"ph": "E", "name": "c"},
+        ]})
+        .to_string();
+        let events: Vec<&RawValue> = extract_json_path(&json, filter).unwrap();
+        let events: Vec<_> = events.iter().map(|v| v.get()).collect();
+        assert_eq!(
+            events,
+            [r#"{"name":"b","ph":"B"}"#, r#"{"name":"c","ph":"E"}"#]
+        );
+
+        let json = json!({ "a": [{"b": [1, 2]}, {"b": [3]}], "n": [1, null, 2.5, -1] }).to_string();
+        let firsts: Vec<u32> = extract_json_path(&json, crate::path!(.a | map(.b[0]))).unwrap();
+        assert_eq!(firsts, [1, 3]);
+        let firsts: Vec<u32> =
+            extract_json_path(&json, crate::path!(.["a"][] | ."b" | .[0])).unwrap();
+        assert_eq!(firsts, [1, 3]);
+        let tail: Vec<f64> = extract_json_path(&json, crate::path!(.n[2:])).unwrap();
+        assert_eq!(tail, [2.5, -1.0]);
+        let middle: (&str, (Slice, Final)) = crate::path!(.n[1:-1]);
+        let middle: Vec<Option<f64>> = extract_json_path(&json, middle).unwrap();
+        assert_eq!(middle, [None, Some(2.5)]);
+        let ns: Vec<f64> = extract_json_path(&json, crate::path!(.a | .. | (.b[0])?)).unwrap();
+        assert_eq!(ns, [1.0, 3.0]);
+        let v: Vec<u32> = extract_json_path(&json, crate::path!(.a[] | .c?)).unwrap();
+        assert!(v.is_empty());
+        let v: Option<u32> = extract_json_path(&json, crate::path!(.a[0].c?)).unwrap();
+        assert_eq!(v, None);
+        let last: (&str, (FromEnd, Final)) = crate::path!(.n[-1]);
+        let last: i32 = extract_json_path(&json, last).unwrap();
+        assert_eq!(last, -1);
+        let first: f64 = extract_json_path(&json, crate::path!(.n[-0])).unwrap();
+        assert_eq!(first, 1.0);
+        let nums: Vec<f64> =
+            extract_json_path(&json, crate::path!(.n | map(select(. != null)))).unwrap();
+        assert_eq!(nums, [1.0, 2.5, -1.0]);
+        let nums: Vec<f64> = extract_json_path(
+            &json,
+            crate::path!(.n[] | select(. != null) | select(-1 != .)),
+        )
+        .unwrap();
+        assert_eq!(nums, [1.0, 2.5]);
+        let nums: Vec<f64> = extract_json_path(
+            &json,
+            crate::path!(.n | map(select(. != null) | select(. >= 1))),
+        )
+        .unwrap();
+        assert_eq!(nums, [1.0, 2.5]);
+        let nums: Vec<f64> =
+            extract_json_path(&json, crate::path!(.n[0:1] | map(select(-1.5 < .)))).unwrap();
+        assert_eq!(nums, [1.0]);
+    }
+}
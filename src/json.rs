@@ -1,16 +1,46 @@
-use std::{marker::PhantomData, vec::IntoIter};
+use std::{cmp::Ordering, marker::PhantomData, num::NonZeroUsize, vec::IntoIter};
 
-use serde::{de, forward_to_deserialize_any};
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny},
+    forward_to_deserialize_any,
+};
 use serde_json::{Value, value::RawValue};
 
-use crate::{FilterChain, MultiMap, MultiVec};
+use crate::{
+    ChainOpt, FilterChain, FromEnd, MultiMap, MultiVec, Slice, TakeWrapper,
+    borrow::Unborrow,
+    content::{Content, Replay},
+    map::{Map, Recurse},
+    map_select::MapSelectEntries,
+    obj::FieldVisitor,
+    optional::Optional,
+    predicate::{
+        And, Contains, EndsWith, Eq, FilterPredicate, Ge, Gt, Has, HasIndex, Le, Lt, Not, NotEq,
+        Number, Or, StartsWith,
+    },
+    raw::RawValue2,
+    select::Select,
+    track::{self, Segment},
+};
 
 #[derive(Debug, Clone)]
 pub enum JsonFieldIndex {
     List(usize),
+    /// `[-n]`, counting from the end of the list.
+    FromEnd(NonZeroUsize),
     Map(String),
 }
 
+impl JsonFieldIndex {
+    /// `[-n]`, where `[-0]` is `[0]` as in jq.
+    pub fn from_end(n: usize) -> Self {
+        match NonZeroUsize::new(n) {
+            Some(n) => JsonFieldIndex::FromEnd(n),
+            None => JsonFieldIndex::List(0),
+        }
+    }
+}
+
 impl From<&str> for JsonFieldIndex {
     fn from(value: &str) -> Self {
         JsonFieldIndex::Map(value.to_string())
@@ -26,55 +56,396 @@ impl From<usize> for JsonFieldIndex {
 #[derive(Debug, Clone)]
 pub enum JsonField {
     Index(JsonFieldIndex),
+    /// Like `Index`, but `null` for a missing key or index, or inside `null`, as in jq.
+    IndexOrNull(JsonFieldIndex),
     List(MultiVec<JsonPath>),
     Map(MultiMap<JsonPath>),
+    Mapped(Map<JsonPath>),
+    Select(Select<JsonPredicate>),
+    /// `with_entries(select(...))`, the entries of a map given as `{"key": k, "value": v}`.
+    SelectEntries(MapSelectEntries<JsonPredicate>),
+    Slice(Slice),
+    /// `[]`, every value of a list or a map.
+    Each,
+    /// `..`, a value and every value nested in it.
+    Recurse,
+    /// `?`, the path is absent instead of failing on a missing key or an unexpected type.
+    /// The path must select a value from the input, rather than construct one.
+    Optional(JsonPath),
+}
+
+#[derive(Debug, Clone)]
+pub enum JsonPredicate {
+    NotEq(NotEq<JsonPath, JsonLiteral>),
+    Eq(Eq<JsonPath, JsonLiteral>),
+    Lt(Lt<JsonPath, JsonLiteral>),
+    Le(Le<JsonPath, JsonLiteral>),
+    Gt(Gt<JsonPath, JsonLiteral>),
+    Ge(Ge<JsonPath, JsonLiteral>),
+    And(And<Box<JsonPredicate>, Box<JsonPredicate>>),
+    Or(Or<Box<JsonPredicate>, Box<JsonPredicate>>),
+    Not(Not<Box<JsonPredicate>>),
+    Has(Has<String>),
+    HasIndex(HasIndex),
+    Contains(Contains<JsonPath, String>),
+    StartsWith(StartsWith<JsonPath, String>),
+    EndsWith(EndsWith<JsonPath, String>),
+    #[cfg(feature = "regex")]
+    Matches(crate::predicate::Matches<JsonPath>),
+}
+
+impl<'de> FilterPredicate<'de> for JsonPredicate {
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match self {
+            JsonPredicate::NotEq(p) => p.filter(deserializer),
+            JsonPredicate::Eq(p) => p.filter(deserializer),
+            JsonPredicate::Lt(p) => p.filter(deserializer),
+            JsonPredicate::Le(p) => p.filter(deserializer),
+            JsonPredicate::Gt(p) => p.filter(deserializer),
+            JsonPredicate::Ge(p) => p.filter(deserializer),
+            JsonPredicate::And(p) => p.filter(deserializer),
+            JsonPredicate::Or(p) => p.filter(deserializer),
+            JsonPredicate::Not(p) => p.filter(deserializer),
+            JsonPredicate::Has(p) => p.filter(deserializer),
+            JsonPredicate::HasIndex(p) => p.filter(deserializer),
+            JsonPredicate::Contains(p) => p.filter(deserializer),
+            JsonPredicate::StartsWith(p) => p.filter(deserializer),
+            JsonPredicate::EndsWith(p) => p.filter(deserializer),
+            #[cfg(feature = "regex")]
+            JsonPredicate::Matches(p) => p.filter(deserializer),
+        }
+    }
+}
+
+/// A value compared in a [`JsonPredicate`].
+///
+/// Numbers compare by value, like [`Number`]. Other values are only ordered against a value of
+/// the same type, so `"a" < 1` is false, as is `"a" >= 1`.
+#[derive(Debug, Clone)]
+pub struct JsonLiteral(pub Value);
+
+impl PartialEq for JsonLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Value::Number(a), Value::Number(b)) => Number::from(a) == Number::from(b),
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl PartialOrd for JsonLiteral {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (&self.0, &other.0) {
+            (Value::Number(a), Value::Number(b)) => Number::from(a).partial_cmp(&Number::from(b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (a, b) => (a == b).then_some(Ordering::Equal),
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for JsonLiteral {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer).map(JsonLiteral)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct JsonPath(pub IntoIter<JsonField>);
 
 impl JsonPath {
-    fn filter_inner<'de, S>(
+    /// This path followed by `rest`.
+    fn then(self, rest: JsonPath) -> JsonPath {
+        JsonPath(self.0.chain(rest.0).collect::<Vec<_>>().into_iter())
+    }
+
+    fn filter_inner<'de, N, S>(
+        self,
+        seed: S,
+        node: N,
+    ) -> Result<Option<S::Value>, serde_json::Error>
+    where
+        N: Node<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let mut visited = Vec::new();
+        let value = self.filter_fields(seed, node, &mut visited);
+        visited
+            .into_iter()
+            .rev()
+            .fold(value, |value, segment| track::at(value, || segment))
+    }
+
+    /// Applies the fields in order, recording the keys and indices it steps into.
+    fn filter_fields<'de, N, S>(
         mut self,
         seed: S,
-        mut raw: &'de RawValue,
-    ) -> Result<S::Value, serde_json::Error>
+        mut node: N,
+        visited: &mut Vec<Segment>,
+    ) -> Result<Option<S::Value>, serde_json::Error>
     where
+        N: Node<'de>,
         S: de::DeserializeSeed<'de>,
     {
         for filter in self.0.by_ref() {
             match filter {
-                JsonField::Index(JsonFieldIndex::List(filter)) => {
-                    raw = filter.filter(PhantomData, raw)?;
+                JsonField::Index(index) => {
+                    node = node.index(&index)?;
+                    visited.push(index.segment());
                 }
-                JsonField::Index(JsonFieldIndex::Map(filter)) => {
-                    raw = filter.filter(PhantomData, raw)?;
+                JsonField::IndexOrNull(index) => {
+                    node = node.lookup(&index)?;
+                    visited.push(index.segment());
                 }
+                // the rest of the path applies to the output of each branch.
                 JsonField::List(filter) => {
-                    let mut list = Vec::with_capacity(filter.0.len());
-                    for filter in filter.0 {
-                        list.push(filter.filter(PhantomData, raw)?);
-                    }
-                    return seed.deserialize(JsonList {
-                        path: self,
-                        iter: list.into_iter(),
-                    });
+                    return seed
+                        .deserialize(JsonList {
+                            path: self,
+                            node,
+                            iter: filter.0.into_iter(),
+                        })
+                        .map(Some);
                 }
                 JsonField::Map(filter) => {
-                    let mut list = Vec::with_capacity(filter.0.len());
-                    for (key, filter) in filter.0 {
-                        list.push((key, filter.filter(PhantomData, raw)?));
+                    return seed
+                        .deserialize(JsonMap {
+                            path: self,
+                            node,
+                            branch: None,
+                            iter: filter.0.into_iter(),
+                        })
+                        .map(Some);
+                }
+                JsonField::Mapped(Map(filter)) => {
+                    return Map(filter.then(self)).filter(seed, node).map(Some);
+                }
+                JsonField::Select(filter) => {
+                    return filter
+                        .filter_opt(ChainOpt { filter: self, seed }, node)
+                        .map(Option::flatten);
+                }
+                // the parser rejects anything after it, as it builds a new map
+                JsonField::SelectEntries(filter) => {
+                    return filter.filter(seed, node).map(Some);
+                }
+                JsonField::Optional(filter) => {
+                    if track::enabled() {
+                        visited.extend(filter.0.as_slice().iter().filter_map(
+                            |field| match field {
+                                JsonField::Index(index) | JsonField::IndexOrNull(index) => {
+                                    Some(index.segment())
+                                }
+                                _ => None,
+                            },
+                        ));
                     }
-                    return seed.deserialize(JsonMap {
-                        path: self,
-                        raw: None,
-                        iter: list.into_iter(),
-                    });
+                    match node.optional(filter)? {
+                        Some(value) => node = value,
+                        None => return Ok(None),
+                    }
+                }
+                JsonField::Each => {
+                    return Map(self).filter(seed, node).map(Some);
+                }
+                JsonField::Recurse => {
+                    return Recurse::new(self).filter(seed, node).map(Some);
+                }
+                // like `Mapped`, over the selected elements only.
+                JsonField::Slice(slice) => {
+                    return (slice, Map(self)).filter(seed, node).map(Some);
                 }
             }
         }
 
-        seed.deserialize(raw)
+        seed.deserialize(node).map(Some)
+    }
+
+    /// Like `filter_inner`, with `null` for an absent [`JsonField::Optional`].
+    fn filter_or_null<'de, N, S>(self, seed: S, node: N) -> Result<S::Value, serde_json::Error>
+    where
+        N: Node<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let mut seed = TakeWrapper(Some(seed));
+        match self.filter_inner(&mut seed, node)? {
+            Some(val) => Ok(val),
+            None => seed.0.take().unwrap().deserialize(Value::Null),
+        }
+    }
+}
+
+impl JsonFieldIndex {
+    fn segment(&self) -> Segment {
+        match self {
+            JsonFieldIndex::List(i) => Segment::Index(*i),
+            JsonFieldIndex::FromEnd(n) => Segment::FromEnd(n.get()),
+            JsonFieldIndex::Map(key) => Segment::Key(key.clone()),
+        }
+    }
+}
+
+/// A value [`JsonPath`] steps into without copying it: raw JSON, or a value buffered from
+/// another format.
+trait Node<'de>: de::Deserializer<'de, Error = serde_json::Error> + Copy {
+    fn index(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error>;
+
+    /// Like `index`, but `null` for a missing key or index, or inside `null`.
+    fn lookup(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error>;
+
+    /// The value `path` selects, `None` if it is absent.
+    fn optional(self, path: JsonPath) -> Result<Option<Self>, serde_json::Error>;
+}
+
+impl<'de> Node<'de> for &'de RawValue {
+    fn index(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        match index {
+            JsonFieldIndex::List(i) => i.filter(PhantomData, self),
+            JsonFieldIndex::FromEnd(n) => FromEnd(*n).filter(PhantomData, self),
+            JsonFieldIndex::Map(key) => key.as_str().filter(PhantomData, self),
+        }
+    }
+
+    fn lookup(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        let container = match index {
+            JsonFieldIndex::Map(_) => b'{',
+            JsonFieldIndex::List(_) | JsonFieldIndex::FromEnd(_) => b'[',
+        };
+        match self.get().trim_start().as_bytes().first() {
+            Some(b'n') => Ok(RawValue::NULL),
+            Some(&first) if first == container => self.index(index).or_else(|_| {
+                track::clear();
+                Ok(RawValue::NULL)
+            }),
+            _ => self.index(index),
+        }
+    }
+
+    fn optional(self, path: JsonPath) -> Result<Option<Self>, serde_json::Error> {
+        Optional(path).filter_opt(PhantomData, self)
+    }
+}
+
+/// Raw JSON owned by the caller, which the output cannot borrow from.
+impl<'a, 'de> Node<'de> for Unborrow<'a, &'a RawValue> {
+    fn index(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        self.0.index(index).map(|raw| Unborrow(raw, PhantomData))
+    }
+
+    fn lookup(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        self.0.lookup(index).map(|raw| Unborrow(raw, PhantomData))
+    }
+
+    fn optional(self, path: JsonPath) -> Result<Option<Self>, serde_json::Error> {
+        let raw = self.0.optional(path)?;
+        Ok(raw.map(|raw| Unborrow(raw, PhantomData)))
+    }
+}
+
+impl<'de> Node<'de> for Replay<'_, 'de> {
+    fn index(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        let found = match (index, self.content()) {
+            (JsonFieldIndex::List(i), Content::Seq(elements)) => elements.get(*i),
+            (JsonFieldIndex::FromEnd(n), Content::Seq(elements)) => {
+                elements.len().checked_sub(n.get()).map(|i| &elements[i])
+            }
+            (JsonFieldIndex::Map(key), Content::Map(entries)) => {
+                let mut found = None;
+                for (k, v) in entries {
+                    if FieldVisitor(key).deserialize(self.nested(k))? {
+                        // a duplicate, which the filter below reports
+                        if found.replace(v).is_some() {
+                            found = None;
+                            break;
+                        }
+                    }
+                }
+                found
+            }
+            _ => None,
+        };
+        if let Some(value) = found {
+            return Ok(self.nested(value));
+        }
+        // the filters word the error
+        let err = match index {
+            JsonFieldIndex::List(i) => i.filter(PhantomData::<IgnoredAny>, self),
+            JsonFieldIndex::FromEnd(n) => FromEnd(*n).filter(PhantomData::<IgnoredAny>, self),
+            JsonFieldIndex::Map(key) => key.as_str().filter(PhantomData::<IgnoredAny>, self),
+        };
+        Err(err
+            .err()
+            .unwrap_or_else(|| de::Error::custom("index not found")))
+    }
+
+    fn lookup(self, index: &JsonFieldIndex) -> Result<Self, serde_json::Error> {
+        static NULL: Content = Content::Unit;
+        match (index, self.content()) {
+            (_, Content::Unit | Content::None) => Ok(self.nested(&NULL)),
+            (JsonFieldIndex::Map(_), Content::Map(_))
+            | (JsonFieldIndex::List(_) | JsonFieldIndex::FromEnd(_), Content::Seq(_)) => {
+                Ok(self.index(index).unwrap_or_else(|_| {
+                    track::clear();
+                    self.nested(&NULL)
+                }))
+            }
+            _ => self.index(index),
+        }
+    }
+
+    fn optional(self, path: JsonPath) -> Result<Option<Self>, serde_json::Error> {
+        let mut node = self;
+        for field in path.0 {
+            let found = match field {
+                JsonField::Index(index) => node.index(&index).ok(),
+                JsonField::IndexOrNull(index) => node.lookup(&index).ok(),
+                JsonField::Optional(path) => node.optional(path)?,
+                JsonField::Select(Select(predicate)) => {
+                    predicate.filter(node).unwrap_or(false).then_some(node)
+                }
+                _ => {
+                    return Err(de::Error::custom(
+                        "`?` over a value that is not JSON only supports keys, indices and `select`",
+                    ));
+                }
+            };
+            match found {
+                Some(found) => node = found,
+                None => {
+                    track::clear();
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(node))
+    }
+}
+
+impl JsonPath {
+    /// Buffers the value to step into it.
+    fn buffered<'de, D, S>(self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        match <RawValue2<'de> as de::Deserialize>::deserialize(deserializer)? {
+            RawValue2::Borrowed(raw) => self.filter_inner(seed, raw),
+            RawValue2::Owned(raw) => self.filter_inner(seed, Unborrow(&*raw, PhantomData)),
+            RawValue2::Content(content, human_readable) => {
+                self.filter_inner(seed, Replay::new(&content, human_readable))
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => <Content as de::Deserialize>::deserialize(raw)
+                .map_err(de::Error::custom)
+                .and_then(|content| self.filter_inner(seed, Replay::new(&content, false))),
+        }
+        .map_err(de::Error::custom)
     }
 }
 
@@ -84,17 +455,182 @@ impl<'de> FilterChain<'de> for JsonPath {
         D: de::Deserializer<'de>,
         S: de::DeserializeSeed<'de>,
     {
-        let raw = <&'de RawValue as de::Deserialize>::deserialize(deserializer)?;
-        self.filter_inner(seed, raw).map_err(de::Error::custom)
+        let mut seed = TakeWrapper(Some(seed));
+        match self.filter_opt(&mut seed, deserializer)? {
+            Some(value) => Ok(value),
+            None => seed
+                .0
+                .take()
+                .unwrap()
+                .deserialize(Value::Null)
+                .map_err(de::Error::custom),
+        }
+    }
+
+    /// Keys, indices and iterations at the start of the path step into the value as it is
+    /// read. It is only buffered from the first field that looks at it more than once.
+    fn filter_opt<D, S>(mut self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let Some(field) = self.0.as_slice().first() else {
+            return seed.deserialize(deserializer).map(Some);
+        };
+        let streamed = matches!(
+            field,
+            JsonField::Index(JsonFieldIndex::Map(_) | JsonFieldIndex::List(_))
+                | JsonField::IndexOrNull(JsonFieldIndex::Map(_) | JsonFieldIndex::List(_))
+                | JsonField::Mapped(_)
+                | JsonField::Slice(_)
+                | JsonField::Each
+        );
+        if !streamed {
+            return self.buffered(seed, deserializer);
+        }
+        match self.0.next().unwrap() {
+            JsonField::Index(JsonFieldIndex::Map(key)) => {
+                (key.as_str(), self).filter_opt(seed, deserializer)
+            }
+            JsonField::Index(JsonFieldIndex::List(i)) => (i, self).filter_opt(seed, deserializer),
+            JsonField::IndexOrNull(index) => deserializer.deserialize_any(OrNull {
+                index,
+                path: self,
+                seed,
+            }),
+            JsonField::Mapped(Map(filter)) => {
+                Map(filter.then(self)).filter(seed, deserializer).map(Some)
+            }
+            JsonField::Slice(slice) => (slice, Map(self)).filter(seed, deserializer).map(Some),
+            JsonField::Each => Map(self).filter(seed, deserializer).map(Some),
+            _ => unreachable!("the field is streamed"),
+        }
+    }
+}
+
+/// Steps into a key or an index as the value is read, like [`JsonField::IndexOrNull`].
+struct OrNull<S> {
+    index: JsonFieldIndex,
+    path: JsonPath,
+    seed: S,
+}
+
+/// The rest of the path after the key or index of an [`OrNull`]. The seed is left in place
+/// if the value is missing.
+struct Rest<'a, S> {
+    path: JsonPath,
+    seed: &'a mut Option<S>,
+}
+
+impl<'de, S> de::DeserializeSeed<'de> for Rest<'_, S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = Option<S::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.path
+            .filter_opt(self.seed.take().unwrap(), deserializer)
     }
 }
 
-struct JsonList<'de> {
+/// The rest of the path over `null`, for a missing key or index.
+fn or_null<'de, S, E>(path: JsonPath, seed: S) -> Result<Option<S::Value>, E>
+where
+    S: de::DeserializeSeed<'de>,
+    E: de::Error,
+{
+    path.filter_opt(seed, de::value::UnitDeserializer::new())
+}
+
+impl<'de, S> de::Visitor<'de> for OrNull<S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = Option<S::Value>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.index {
+            JsonFieldIndex::Map(key) => write!(f, "a map containing element {key:?}, or null"),
+            _ => write!(f, "a sequence or null"),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        or_null(self.path, self.seed)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        or_null(self.path, self.seed)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let OrNull {
+            index: JsonFieldIndex::Map(key),
+            path,
+            seed,
+        } = self
+        else {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+        };
+        let mut seed = Some(seed);
+        let mut found = None;
+        while let Some(matched) = map.next_key_seed(FieldVisitor(&key))? {
+            if matched && found.is_none() {
+                let rest = Rest {
+                    path: path.clone(),
+                    seed: &mut seed,
+                };
+                let value = map.next_value_seed(rest);
+                found = Some(track::at(value, || Segment::Key(key.clone()))?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        match found {
+            Some(value) => Ok(value),
+            None => or_null(path, seed.take().unwrap()),
+        }
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let OrNull {
+            index: JsonFieldIndex::List(i),
+            path,
+            seed,
+        } = self
+        else {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &self));
+        };
+        for _ in 0..i {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return or_null(path, seed);
+            }
+        }
+        let mut seed = Some(seed);
+        let rest = Rest {
+            path: path.clone(),
+            seed: &mut seed,
+        };
+        let value = seq.next_element_seed(rest);
+        let Some(value) = track::at(value, || Segment::Index(i))? else {
+            return or_null(path, seed.take().unwrap());
+        };
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(value)
+    }
+}
+
+struct JsonList<N> {
     path: JsonPath,
-    iter: IntoIter<&'de RawValue>,
+    node: N,
+    iter: IntoIter<JsonPath>,
 }
 
-impl<'de> de::Deserializer<'de> for JsonList<'de> {
+impl<'de, N: Node<'de>> de::Deserializer<'de> for JsonList<N> {
     type Error = serde_json::Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -111,27 +647,35 @@ impl<'de> de::Deserializer<'de> for JsonList<'de> {
     }
 }
 
-impl<'de> de::SeqAccess<'de> for JsonList<'de> {
+impl<'de, N: Node<'de>> de::SeqAccess<'de> for JsonList<N> {
     type Error = serde_json::Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
     where
         T: de::DeserializeSeed<'de>,
     {
-        let Some(raw) = self.iter.next() else {
-            return Ok(None);
-        };
-        self.path.clone().filter_inner(seed, raw).map(Some)
+        // absent branches are left out.
+        let mut seed = TakeWrapper(Some(seed));
+        for branch in self.iter.by_ref() {
+            let value = branch
+                .then(self.path.clone())
+                .filter_inner(&mut seed, self.node)?;
+            if value.is_some() {
+                return Ok(value);
+            }
+        }
+        Ok(None)
     }
 }
 
-struct JsonMap<'de> {
+struct JsonMap<N> {
     path: JsonPath,
-    raw: Option<&'de RawValue>,
-    iter: IntoIter<(String, &'de RawValue)>,
+    node: N,
+    branch: Option<JsonPath>,
+    iter: IntoIter<(String, JsonPath)>,
 }
 
-impl<'de> de::Deserializer<'de> for JsonMap<'de> {
+impl<'de, N: Node<'de>> de::Deserializer<'de> for JsonMap<N> {
     type Error = serde_json::Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -148,7 +692,7 @@ impl<'de> de::Deserializer<'de> for JsonMap<'de> {
     }
 }
 
-impl<'de> de::MapAccess<'de> for JsonMap<'de> {
+impl<'de, N: Node<'de>> de::MapAccess<'de> for JsonMap<N> {
     type Error = serde_json::Error;
 
     fn next_entry_seed<K, V>(
@@ -160,11 +704,13 @@ impl<'de> de::MapAccess<'de> for JsonMap<'de> {
         K: de::DeserializeSeed<'de>,
         V: de::DeserializeSeed<'de>,
     {
-        let Some((key, raw)) = self.iter.next() else {
+        let Some((key, branch)) = self.iter.next() else {
             return Ok(None);
         };
         let key = kseed.deserialize(Value::String(key))?;
-        let value = self.path.clone().filter_inner(vseed, raw)?;
+        let value = branch
+            .then(self.path.clone())
+            .filter_or_null(vseed, self.node)?;
         Ok(Some((key, value)))
     }
 
@@ -172,10 +718,10 @@ impl<'de> de::MapAccess<'de> for JsonMap<'de> {
     where
         K: de::DeserializeSeed<'de>,
     {
-        let Some((key, raw)) = self.iter.next() else {
+        let Some((key, branch)) = self.iter.next() else {
             return Ok(None);
         };
-        self.raw = Some(raw);
+        self.branch = Some(branch);
         seed.deserialize(Value::String(key)).map(Some)
     }
 
@@ -183,30 +729,134 @@ impl<'de> de::MapAccess<'de> for JsonMap<'de> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        self.path.clone().filter_inner(seed, self.raw.unwrap())
-    }
-}
-
-// #[macro_export]
-// macro_rules! json_path {
-//     ($($tt:tt)*) => { $crate::json::JsonPath($crate::json_path_inner!(vec![]; $($tt)*).into_iter()) }
-// }
-
-// #[doc(hidden)]
-// #[macro_export]
-// macro_rules! json_path_inner {
-//     ($fields:expr; [$($tt:tt)*]) => {{
-//         let mut fields = $fields;
-//         fields.push($crate::json::JsonField::List($crate::MultiVec($crate::json_path_inner!(vec![]$($tt)*))));
-//         fields
-//     }};
-//     ($fields:expr; @[$lit:literal] $($tt:tt)*) => {{
-//         let mut fields = $fields;
-//         fields.push($crate::json::JsonField::Index($lit.into()));
-//         $crate::json_path_inner!(fields; @$($tt)*)
-//     }};
-//     ($fields:expr;) => { $fields }
-// }
+        let branch = self.branch.take().unwrap();
+        branch
+            .then(self.path.clone())
+            .filter_or_null(seed, self.node)
+    }
+}
+
+/// Builds a runtime [`JsonPath`].
+///
+/// A path starts with `@` and is followed by any number of segments:
+/// * `["key"]` or `[0]` indexes into a map or a sequence,
+/// * `[@path, @path]` applies each path and collects the results into a sequence,
+/// * `{"key": @path, "key2": @path}` applies each path and collects the results into a map.
+///
+/// ```
+/// # use serde_path::json_path;
+/// let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
+/// let path = json_path!(@["b"]{"one": @["c"][1], "two": @["d"][0]});
+/// ```
+///
+/// Malformed paths are rejected at compile time:
+///
+/// ```compile_fail
+/// # use serde_path::json_path;
+/// let path = json_path!(@["b"].c);
+/// ```
+#[macro_export]
+macro_rules! json_path {
+    (@ $($tt:tt)*) => {
+        $crate::json::JsonPath($crate::json_path_inner!([] $($tt)*).into_iter())
+    };
+    ($($tt:tt)*) => {
+        compile_error!("a json path must start with `@`")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! json_path_inner {
+    ([$($fields:expr,)*]) => {
+        ::std::vec![$($fields,)*]
+    };
+    ([$($fields:expr,)*] .. $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::Recurse,
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] [] $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::Each,
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] [- $index:literal] $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::Index($crate::json::JsonFieldIndex::from_end($index)),
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] [$index:literal] $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::Index($crate::json::JsonFieldIndex::from($index)),
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] [@ $($list:tt)*] $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::List($crate::MultiVec(
+                $crate::json_path_inner!(@split list [] [] @ $($list)*)
+            )),
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::json_path_inner!([
+            $($fields,)*
+            $crate::json::JsonField::Map($crate::MultiMap(
+                $crate::json_path_inner!(@split map [] [] $($map)*)
+            )),
+        ] $($rest)*)
+    };
+    ([$($fields:expr,)*] [$($bad:tt)*] $($rest:tt)*) => {
+        compile_error!(concat!(
+            "expected `[\"key\"]`, `[0]`, `[]` or `[@path, ...]`, found `[",
+            stringify!($($bad)*),
+            "]`"
+        ))
+    };
+    ([$($fields:expr,)*] $bad:tt $($rest:tt)*) => {
+        compile_error!(concat!(
+            "expected `[...]`, `{...}` or `..` in json path, found `",
+            stringify!($bad),
+            "`"
+        ))
+    };
+
+    // splits the contents of `[...]` or `{...}` on top-level commas.
+    (@split $kind:ident [$($items:expr,)*] [$($current:tt)+] , $($rest:tt)*) => {
+        $crate::json_path_inner!(@split $kind [
+            $($items,)*
+            $crate::json_path_inner!(@item $kind $($current)+),
+        ] [] $($rest)*)
+    };
+    (@split $kind:ident [$($items:expr,)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::json_path_inner!(@split $kind [$($items,)*] [$($current)* $next] $($rest)*)
+    };
+    (@split $kind:ident [$($items:expr,)*] []) => {
+        ::std::vec![$($items,)*]
+    };
+    (@split $kind:ident [$($items:expr,)*] [$($current:tt)+]) => {
+        ::std::vec![$($items,)* $crate::json_path_inner!(@item $kind $($current)+),]
+    };
+
+    (@item list $($path:tt)+) => {
+        $crate::json_path!($($path)+)
+    };
+    (@item map $key:literal : $($path:tt)+) => {
+        (::std::string::String::from($key), $crate::json_path!($($path)+))
+    };
+    (@item map $($bad:tt)*) => {
+        compile_error!(concat!(
+            "expected `\"key\": @path`, found `",
+            stringify!($($bad)*),
+            "`"
+        ))
+    };
+}
 
 #[cfg(test)]
 mod tests {
@@ -214,11 +864,7 @@ mod tests {
 
     use serde_json::{Serializer, Value, json};
 
-    use crate::{
-        FilterChain,
-        json::{JsonField, JsonPath},
-        json_ser::JsonSer,
-    };
+    use crate::{FilterChain, json_ser::JsonSer};
 
     fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
     where
@@ -243,24 +889,30 @@ mod tests {
         })
         .to_string();
 
-        // let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
-        let path = JsonPath(
-            vec![
-                JsonField::Index("b".into()),
-                JsonField::List(crate::MultiVec(vec![
-                    JsonPath(
-                        vec![JsonField::Index("c".into()), JsonField::Index(1.into())].into_iter(),
-                    ),
-                    JsonPath(
-                        vec![JsonField::Index("d".into()), JsonField::Index(0.into())].into_iter(),
-                    ),
-                ])),
-            ]
-            .into_iter(),
-        );
+        let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
 
         let fields: Value = extract_json_path(&json, path).unwrap();
         assert_eq!(fields, json!([3, 5]));
+
+        let path = json_path!(@["b"][@["c"][-1], @["d"][-1]]);
+        let fields: Value = extract_json_path(&json, path).unwrap();
+        assert_eq!(fields, json!([4, 5]));
+
+        let path = json_path!(@["b"]["c"][-0]);
+        let fields: Value = extract_json_path(&json, path).unwrap();
+        assert_eq!(fields, json!(2));
+
+        let path = json_path!(@["b"][][0]);
+        let fields: Value = extract_json_path(&json, path).unwrap();
+        assert_eq!(fields, json!([2, 5]));
+
+        let path = json_path!(@["b"]["c"]..);
+        let fields: Value = extract_json_path(&json, path).unwrap();
+        assert_eq!(fields, json!([[2, 3, 4], 2, 3, 4]));
+
+        let path = json_path!(@["b"]..[0]);
+        let err = extract_json_path::<_, Value>(&json, path).unwrap_err();
+        assert!(err.to_string().contains("expected a sequence"), "{err}");
     }
 
     #[test]
@@ -275,21 +927,7 @@ mod tests {
         })
         .to_string();
 
-        // let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
-        let path = JsonPath(
-            vec![
-                JsonField::Index("b".into()),
-                JsonField::List(crate::MultiVec(vec![
-                    JsonPath(
-                        vec![JsonField::Index("c".into()), JsonField::Index(1.into())].into_iter(),
-                    ),
-                    JsonPath(
-                        vec![JsonField::Index("d".into()), JsonField::Index(0.into())].into_iter(),
-                    ),
-                ])),
-            ]
-            .into_iter(),
-        );
+        let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
 
         let ser = Serializer::pretty(Vec::new());
         let output = path
@@ -299,4 +937,24 @@ mod tests {
 
         assert_eq!(output, "[\n  3,\n  5\n]");
     }
+
+    #[test]
+    fn map() {
+        let json = json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [{"e": 5}]
+            },
+        })
+        .to_string();
+
+        let path = json_path!(@["b"]{
+            "one": @["c"][1],
+            "two": @["d"][0][@["e"], @],
+        });
+
+        let fields: Value = extract_json_path(&json, path).unwrap();
+        assert_eq!(fields, json!({"one": 3, "two": [5, {"e": 5}]}));
+    }
 }
@@ -129,9 +129,9 @@ impl<'de, S: Serializer> Visitor<'de> for SerWrapper<S> {
     where
         D: Deserializer<'de>,
     {
-        self.0
-            .serialize_some(&DeWrapper(Cell::new(Some(deserializer))))
-            .map_err(DeError::custom)
+        let value = DeWrapper::new(deserializer);
+        let result = self.0.serialize_some(&value);
+        value.result(result)
     }
 
     fn visit_unit<E: DeError>(self) -> Result<S::Ok, E> {
@@ -148,9 +148,9 @@ impl<'de, S: SerializeSeq> DeserializeSeed<'de> for SerSeqWrapper<S> {
     where
         D: Deserializer<'de>,
     {
-        self.0
-            .serialize_element(&DeWrapper(Cell::new(Some(deserializer))))
-            .map_err(DeError::custom)?;
+        let element = DeWrapper::new(deserializer);
+        let result = self.0.serialize_element(&element);
+        element.result(result)?;
         Ok(self)
     }
 }
@@ -164,9 +164,9 @@ impl<'de, S: SerializeMap> DeserializeSeed<'de> for SerMapKeyWrapper<S> {
     where
         D: Deserializer<'de>,
     {
-        self.0
-            .serialize_key(&DeWrapper(Cell::new(Some(deserializer))))
-            .map_err(DeError::custom)?;
+        let key = DeWrapper::new(deserializer);
+        let result = self.0.serialize_key(&key);
+        key.result(result)?;
         Ok(SerMapValueWrapper(self.0))
     }
 }
@@ -180,26 +180,50 @@ impl<'de, S: SerializeMap> DeserializeSeed<'de> for SerMapValueWrapper<S> {
     where
         D: Deserializer<'de>,
     {
-        self.0
-            .serialize_value(&DeWrapper(Cell::new(Some(deserializer))))
-            .map_err(DeError::custom)?;
+        let value = DeWrapper::new(deserializer);
+        let result = self.0.serialize_value(&value);
+        value.result(result)?;
         Ok(SerMapKeyWrapper(self.0))
     }
 }
 
-struct DeWrapper<D>(Cell<Option<D>>);
+/// A value being read, serialized as it is.
+///
+/// An error reading it only reaches the serializer as a message, so it is kept to be given
+/// back as it was, such as a syntax error of the input.
+struct DeWrapper<'de, D: Deserializer<'de>> {
+    deserializer: Cell<Option<D>>,
+    error: Cell<Option<D::Error>>,
+}
+
+impl<'de, D: Deserializer<'de>> DeWrapper<'de, D> {
+    fn new(deserializer: D) -> Self {
+        DeWrapper {
+            deserializer: Cell::new(Some(deserializer)),
+            error: Cell::new(None),
+        }
+    }
+
+    fn result<T, E: SerError>(self, result: Result<T, E>) -> Result<T, D::Error> {
+        result.map_err(|err| self.error.take().unwrap_or_else(|| DeError::custom(err)))
+    }
+}
 
-impl<'de, D: Deserializer<'de>> Serialize for DeWrapper<D> {
+impl<'de, D: Deserializer<'de>> Serialize for DeWrapper<'de, D> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        let deserializer = self
+            .deserializer
+            .take()
+            .expect("DeWrapper serialize must only be used once");
         SerWrapper(serializer)
-            .deserialize(
-                self.0
-                    .take()
-                    .expect("DeWrapper serialize must only be used once"),
-            )
-            .map_err(SerError::custom)
+            .deserialize(deserializer)
+            .map_err(|err| {
+                let message = S::Error::custom(&err);
+                self.error.set(Some(err));
+                message
+            })
     }
 }
@@ -0,0 +1,1380 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use serde::de::{self, DeserializeSeed, value::SeqDeserializer};
+use serde_json::{Number, Value};
+
+use crate::{
+    FilterChain, Slice, TakeWrapper,
+    content::{Content, Replay},
+    json::{JsonField, JsonFieldIndex, JsonPath},
+};
+
+/// Parses an RFC 9535 JSONPath query.
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    let mut parser = Parser { input, pos: 0 };
+    if !parser.eat("$") {
+        return Err(parser.error(ParseErrorKind::Expected("`$`")));
+    }
+    let segments = parser.segments()?;
+    if parser.pos < input.len() {
+        return Err(parser.error(ParseErrorKind::Expected("a segment or end of query")));
+    }
+    Ok(Query { segments })
+}
+
+/// A compiled JSONPath query.
+///
+/// As a [`FilterChain`] it yields the resulting nodelist as a sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    /// The leading segments selecting a single name or index, as a [`JsonPath`] stepping into
+    /// the input, and the segments left to evaluate over the value it selects.
+    ///
+    /// Filters querying the root from `$` need the whole input, so nothing is stepped into.
+    fn split(&self) -> (JsonPath, &[Segment]) {
+        let mut fields = Vec::new();
+        if !self.segments.iter().any(Segment::uses_root) {
+            for segment in &self.segments {
+                let index = match segment {
+                    Segment::Child(selectors) => match selectors[..] {
+                        [Selector::Name(ref name)] => JsonFieldIndex::Map(name.clone()),
+                        [Selector::Index(i)] if i >= 0 => JsonFieldIndex::List(i as usize),
+                        [Selector::Index(i)] => JsonFieldIndex::from_end(i.unsigned_abs() as usize),
+                        _ => break,
+                    },
+                    Segment::Descendant(_) => break,
+                };
+                fields.push(JsonField::Index(index));
+            }
+        }
+
+        let rest = &self.segments[fields.len()..];
+        let path = match fields.is_empty() {
+            true => Vec::new(),
+            // an absent name or index is an empty nodelist
+            false => vec![JsonField::Optional(JsonPath(fields.into_iter()))],
+        };
+        (JsonPath(path.into_iter()), rest)
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+impl<'de> FilterChain<'de> for &Query {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let (path, segments) = self.split();
+        let mut seed = TakeWrapper(Some(seed));
+        let nodes = Nodes {
+            segments,
+            seed: &mut seed,
+        };
+        match path.filter_opt(nodes, deserializer)? {
+            Some(val) => Ok(val),
+            None => seed
+                .0
+                .take()
+                .unwrap()
+                .deserialize(SeqDeserializer::<_, serde_json::Error>::new(
+                    std::iter::empty::<Value>(),
+                ))
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+impl<'de> FilterChain<'de> for Query {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        (&self).filter(seed, deserializer)
+    }
+}
+
+/// Evaluates the segments over the value, buffered once, passing the nodelist to the seed.
+struct Nodes<'q, S> {
+    segments: &'q [Segment],
+    seed: S,
+}
+
+impl<'de, S> DeserializeSeed<'de> for Nodes<'_, S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let human_readable = deserializer.is_human_readable();
+        let root = <Content as de::Deserialize>::deserialize(deserializer)?;
+        let nodes = eval_segments(self.segments, &root, vec![&root]).map_err(de::Error::custom)?;
+        let nodes = nodes
+            .into_iter()
+            .map(|node| Replay::new(node, human_readable));
+        self.seed
+            .deserialize(SeqDeserializer::<_, serde_json::Error>::new(nodes))
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: usize,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Byte offset into the query.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Expected(&'static str),
+    InvalidLiteral(&'static str),
+    /// An integer outside of the I-JSON range `±(2^53 - 1)`.
+    OutOfRange,
+    UnknownFunction(String),
+    /// An expression that is syntactically valid but not well-typed, such as
+    /// comparing a non-singular query.
+    Type(&'static str),
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Expected(expected) => write!(f, "expected {expected}"),
+            ParseErrorKind::InvalidLiteral(err) => write!(f, "invalid literal: {err}"),
+            ParseErrorKind::OutOfRange => f.write_str("integer out of range"),
+            ParseErrorKind::UnknownFunction(name) => write!(f, "unknown function `{name}`"),
+            ParseErrorKind::Type(err) => write!(f, "type error: {err}"),
+            ParseErrorKind::Unsupported(construct) => {
+                write!(f, "unsupported construct: {construct}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Slice),
+    Filter(Logical),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Logical {
+    Or(Vec<Logical>),
+    And(Vec<Logical>),
+    Not(Box<Logical>),
+    Compare(Comparable, CompareOp, Comparable),
+    Exists(FilterQuery),
+    Function(FunctionCall),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparable {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(FunctionCall),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FilterQuery {
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+impl FilterQuery {
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| match segment {
+            Segment::Child(selectors) => {
+                matches!(selectors[..], [Selector::Name(_) | Selector::Index(_)])
+            }
+            Segment::Descendant(_) => false,
+        })
+    }
+
+    fn uses_root(&self) -> bool {
+        !self.relative || self.segments.iter().any(Segment::uses_root)
+    }
+}
+
+impl Segment {
+    /// Whether a filter in the segment queries the root from `$`.
+    fn uses_root(&self) -> bool {
+        let (Segment::Child(selectors) | Segment::Descendant(selectors)) = self;
+        selectors.iter().any(|selector| match selector {
+            Selector::Filter(logical) => logical.uses_root(),
+            _ => false,
+        })
+    }
+}
+
+impl Logical {
+    fn uses_root(&self) -> bool {
+        match self {
+            Logical::Or(operands) | Logical::And(operands) => {
+                operands.iter().any(Logical::uses_root)
+            }
+            Logical::Not(expr) => expr.uses_root(),
+            Logical::Compare(lhs, _, rhs) => lhs.uses_root() || rhs.uses_root(),
+            Logical::Exists(query) => query.uses_root(),
+            Logical::Function(call) => call.uses_root(),
+        }
+    }
+}
+
+impl Comparable {
+    fn uses_root(&self) -> bool {
+        match self {
+            Comparable::Literal(_) => false,
+            Comparable::Query(query) => query.uses_root(),
+            Comparable::Function(call) => call.uses_root(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Length,
+    Count,
+    Match,
+    Search,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Value,
+    Logical,
+    Nodes,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "length" => Some(Function::Length),
+            "count" => Some(Function::Count),
+            "match" => Some(Function::Match),
+            "search" => Some(Function::Search),
+            "value" => Some(Function::Value),
+            _ => None,
+        }
+    }
+
+    fn params(self) -> &'static [Type] {
+        match self {
+            Function::Length => &[Type::Value],
+            Function::Count | Function::Value => &[Type::Nodes],
+            Function::Match | Function::Search => &[Type::Value, Type::Value],
+        }
+    }
+
+    fn result(self) -> Type {
+        match self {
+            Function::Length | Function::Count | Function::Value => Type::Value,
+            Function::Match | Function::Search => Type::Logical,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FunctionCall {
+    function: Function,
+    args: Vec<Argument>,
+}
+
+impl FunctionCall {
+    fn uses_root(&self) -> bool {
+        self.args.iter().any(|arg| match arg {
+            Argument::Literal(_) => false,
+            Argument::Query(query) => query.uses_root(),
+            Argument::Logical(logical) => logical.uses_root(),
+            Argument::Function(call) => call.uses_root(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Literal(Value),
+    Query(FilterQuery),
+    Logical(Logical),
+    Function(FunctionCall),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.error_at(self.pos, kind)
+    }
+
+    fn error_at(&self, offset: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { kind, offset }
+    }
+
+    /// RFC 9535 only allows space, tab, line feed and carriage return.
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        let trimmed = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+        self.pos += rest.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(ParseErrorKind::Expected(expected)))
+        }
+    }
+
+    /// `*(S segment)`, leaving any trailing whitespace unconsumed.
+    fn segments(&mut self) -> Result<Vec<Segment>, ParseError> {
+        let mut segments = Vec::new();
+        loop {
+            let start = self.pos;
+            self.skip_ws();
+            if self.eat("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.bracketed()?,
+                    Some('*') => {
+                        self.pos += 1;
+                        vec![Selector::Wildcard]
+                    }
+                    _ => vec![Selector::Name(self.member_name()?)],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                let selector = if self.eat("*") {
+                    Selector::Wildcard
+                } else {
+                    Selector::Name(self.member_name()?)
+                };
+                segments.push(Segment::Child(vec![selector]));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracketed()?));
+            } else {
+                self.pos = start;
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn member_name(&mut self) -> Result<String, ParseError> {
+        let rest = self.rest();
+        let is_first = |c: char| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+        let end = rest
+            .char_indices()
+            .find(|&(i, c)| !(is_first(c) || (i > 0 && c.is_ascii_digit())))
+            .map_or(rest.len(), |(i, _)| i);
+        if end == 0 {
+            return Err(self.error(ParseErrorKind::Expected("a member name")));
+        }
+        self.pos += end;
+        Ok(rest[..end].to_owned())
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>, ParseError> {
+        self.expect("[", "`[`")?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_ws();
+            selectors.push(self.selector()?);
+            self.skip_ws();
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",", "`,` or `]`")?;
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, ParseError> {
+        match self.peek() {
+            Some('\'' | '"') => self.string().map(Selector::Name),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.skip_ws();
+                self.logical_or().map(Selector::Filter)
+            }
+            Some('-' | '0'..='9' | ':') => self.index_or_slice(),
+            _ => Err(self.error(ParseErrorKind::Expected("a selector"))),
+        }
+    }
+
+    fn index_or_slice(&mut self) -> Result<Selector, ParseError> {
+        let start = self.optional_int()?;
+        self.skip_ws();
+        if !self.eat(":") {
+            return match start {
+                Some(index) => Ok(Selector::Index(index)),
+                None => Err(self.error(ParseErrorKind::Expected("an index"))),
+            };
+        }
+        self.skip_ws();
+        let end = self.optional_int()?;
+        self.skip_ws();
+        let step = if self.eat(":") {
+            self.skip_ws();
+            self.optional_int()?
+        } else {
+            None
+        };
+        // integers are within ±(2^53 - 1), saturating only matters on 16 and 32 bit targets.
+        let isize =
+            |n: i64| isize::try_from(n).unwrap_or(if n < 0 { isize::MIN } else { isize::MAX });
+        Ok(Selector::Slice(Slice {
+            start: start.map(isize),
+            end: end.map(isize),
+            step: step.map_or(1, isize),
+        }))
+    }
+
+    fn optional_int(&mut self) -> Result<Option<i64>, ParseError> {
+        match self.peek() {
+            Some('-' | '0'..='9') => self.int().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// `"0" / (["-"] DIGIT1 *DIGIT)` within the I-JSON range.
+    fn int(&mut self) -> Result<i64, ParseError> {
+        const MAX: i64 = (1 << 53) - 1;
+
+        let start = self.pos;
+        let rest = self.rest();
+        let digits_start = usize::from(rest.starts_with('-'));
+        let len = rest[digits_start..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(rest.len(), |i| i + digits_start);
+        let digits = &rest[digits_start..len];
+        if digits.is_empty() {
+            return Err(self.error(ParseErrorKind::Expected("an integer")));
+        }
+        if digits.starts_with('0') && (digits.len() > 1 || digits_start == 1) {
+            return Err(self.error(ParseErrorKind::InvalidLiteral("leading zero")));
+        }
+        self.pos += len;
+        match rest[..len].parse::<i64>() {
+            Ok(n) if (-MAX..=MAX).contains(&n) => Ok(n),
+            _ => Err(self.error_at(start, ParseErrorKind::OutOfRange)),
+        }
+    }
+
+    /// A single or double quoted string literal.
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let mut chars = self.rest().char_indices();
+        let Some((_, quote)) = chars.next() else {
+            return Err(self.error(ParseErrorKind::Expected("a string")));
+        };
+
+        let mut out = String::new();
+        loop {
+            let Some((i, c)) = chars.next() else {
+                return Err(
+                    self.error_at(start, ParseErrorKind::InvalidLiteral("unterminated string"))
+                );
+            };
+            let invalid = |kind| self.error_at(start + i, ParseErrorKind::InvalidLiteral(kind));
+            match c {
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('/') => '/',
+                        Some('\\') => '\\',
+                        Some(c) if c == quote => c,
+                        Some('u') => {
+                            let high = hex4(&mut chars)
+                                .ok_or_else(|| invalid("invalid unicode escape"))?;
+                            let code = match high {
+                                0xD800..=0xDBFF => {
+                                    let low = (chars.next().map(|(_, c)| c) == Some('\\')
+                                        && chars.next().map(|(_, c)| c) == Some('u'))
+                                    .then(|| hex4(&mut chars))
+                                    .flatten()
+                                    .filter(|low| (0xDC00..=0xDFFF).contains(low))
+                                    .ok_or_else(|| invalid("unpaired surrogate"))?;
+                                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                                }
+                                0xDC00..=0xDFFF => return Err(invalid("unpaired surrogate")),
+                                code => code,
+                            };
+                            char::from_u32(code).ok_or_else(|| invalid("invalid unicode escape"))?
+                        }
+                        _ => return Err(invalid("invalid escape")),
+                    };
+                    out.push(escaped);
+                }
+                c if c < '\u{20}' => return Err(invalid("unescaped control character")),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// `1`, `-0.5e3`, following RFC 9535 `number`.
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| {
+                !(c.is_ascii_digit()
+                    || c == '.'
+                    || c == 'e'
+                    || c == 'E'
+                    || (matches!(c, '-' | '+') && (i == 0 || matches!(&rest[i - 1..i], "e" | "E"))))
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let text = &rest[..len];
+        // serde_json is stricter than RFC 9535 only in rejecting `1.` and `.1`,
+        // which the RFC rejects too.
+        let value = serde_json::from_str::<Number>(text)
+            .map_err(|_| self.error_at(start, ParseErrorKind::InvalidLiteral("invalid number")))?;
+        self.pos += len;
+        Ok(Value::Number(value))
+    }
+
+    fn literal(&mut self) -> Result<Option<Value>, ParseError> {
+        match self.peek() {
+            Some('\'' | '"') => self.string().map(|s| Some(Value::String(s))),
+            Some('-' | '0'..='9') => self.number().map(Some),
+            _ => {
+                for (keyword, value) in [
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                    ("null", Value::Null),
+                ] {
+                    let rest = self.rest();
+                    if rest.starts_with(keyword)
+                        && !rest[keyword.len()..]
+                            .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        self.pos += keyword.len();
+                        return Ok(Some(value));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn logical_or(&mut self) -> Result<Logical, ParseError> {
+        let mut operands = vec![self.logical_and()?];
+        loop {
+            let start = self.pos;
+            self.skip_ws();
+            if !self.eat("||") {
+                self.pos = start;
+                break;
+            }
+            self.skip_ws();
+            operands.push(self.logical_and()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Logical::Or(operands),
+        })
+    }
+
+    fn logical_and(&mut self) -> Result<Logical, ParseError> {
+        let mut operands = vec![self.basic()?];
+        loop {
+            let start = self.pos;
+            self.skip_ws();
+            if !self.eat("&&") {
+                self.pos = start;
+                break;
+            }
+            self.skip_ws();
+            operands.push(self.basic()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Logical::And(operands),
+        })
+    }
+
+    fn basic(&mut self) -> Result<Logical, ParseError> {
+        if self.eat("!") {
+            self.skip_ws();
+            let start = self.pos;
+            let expr = if self.peek() == Some('(') {
+                self.paren()?
+            } else {
+                match self.comparable()? {
+                    Comparable::Query(query) => Logical::Exists(query),
+                    Comparable::Function(call) => self.test_function(start, call)?,
+                    Comparable::Literal(_) => {
+                        return Err(self.error_at(
+                            start,
+                            ParseErrorKind::Type("literals are not logical expressions"),
+                        ));
+                    }
+                }
+            };
+            return Ok(Logical::Not(Box::new(expr)));
+        }
+        if self.peek() == Some('(') {
+            return self.paren();
+        }
+
+        let start = self.pos;
+        let lhs = self.comparable()?;
+        let before_op = self.pos;
+        self.skip_ws();
+        let Some(op) = self.compare_op() else {
+            self.pos = before_op;
+            return match lhs {
+                Comparable::Query(query) => Ok(Logical::Exists(query)),
+                Comparable::Function(call) => self.test_function(start, call),
+                Comparable::Literal(_) => {
+                    Err(self.error(ParseErrorKind::Expected("a comparison operator")))
+                }
+            };
+        };
+        self.check_comparable(start, &lhs)?;
+        self.skip_ws();
+        let rhs_start = self.pos;
+        let rhs = self.comparable()?;
+        self.check_comparable(rhs_start, &rhs)?;
+        Ok(Logical::Compare(lhs, op, rhs))
+    }
+
+    fn paren(&mut self) -> Result<Logical, ParseError> {
+        self.expect("(", "`(`")?;
+        self.skip_ws();
+        let expr = self.logical_or()?;
+        self.skip_ws();
+        self.expect(")", "`)`")?;
+        Ok(expr)
+    }
+
+    fn compare_op(&mut self) -> Option<CompareOp> {
+        for (token, op) in [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ] {
+            if self.eat(token) {
+                return Some(op);
+            }
+        }
+        None
+    }
+
+    fn test_function(&self, start: usize, call: FunctionCall) -> Result<Logical, ParseError> {
+        match call.function.result() {
+            Type::Logical | Type::Nodes => Ok(Logical::Function(call)),
+            Type::Value => Err(self.error_at(
+                start,
+                ParseErrorKind::Type("function result must be compared"),
+            )),
+        }
+    }
+
+    fn check_comparable(&self, start: usize, comparable: &Comparable) -> Result<(), ParseError> {
+        match comparable {
+            Comparable::Literal(_) => Ok(()),
+            Comparable::Query(query) if query.is_singular() => Ok(()),
+            Comparable::Query(_) => Err(self.error_at(
+                start,
+                ParseErrorKind::Type("only singular queries can be compared"),
+            )),
+            Comparable::Function(call) if call.function.result() == Type::Value => Ok(()),
+            Comparable::Function(_) => Err(self.error_at(
+                start,
+                ParseErrorKind::Type("function does not return a value"),
+            )),
+        }
+    }
+
+    /// A literal, filter query or function call, without checking how it may be used.
+    fn comparable(&mut self) -> Result<Comparable, ParseError> {
+        if let Some(value) = self.literal()? {
+            return Ok(Comparable::Literal(value));
+        }
+        match self.peek() {
+            Some('@' | '$') => self.filter_query().map(Comparable::Query),
+            Some('a'..='z') => self.function().map(Comparable::Function),
+            _ => Err(self.error(ParseErrorKind::Expected("a query, literal or function"))),
+        }
+    }
+
+    fn filter_query(&mut self) -> Result<FilterQuery, ParseError> {
+        let relative = match self.peek() {
+            Some('@') => true,
+            Some('$') => false,
+            _ => return Err(self.error(ParseErrorKind::Expected("`@` or `$`"))),
+        };
+        self.pos += 1;
+        let segments = self.segments()?;
+        Ok(FilterQuery { relative, segments })
+    }
+
+    fn function(&mut self) -> Result<FunctionCall, ParseError> {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        self.pos += len;
+        if self.peek() != Some('(') {
+            return Err(self.error(ParseErrorKind::Expected("`(`")));
+        }
+        let function = Function::from_name(name).ok_or_else(|| {
+            self.error_at(start, ParseErrorKind::UnknownFunction(name.to_owned()))
+        })?;
+        if matches!(function, Function::Match | Function::Search) && !cfg!(feature = "regex") {
+            return Err(self.error_at(start, ParseErrorKind::Unsupported("regular expressions")));
+        }
+        self.pos += 1;
+
+        let mut args = Vec::new();
+        for (i, &param) in function.params().iter().enumerate() {
+            self.skip_ws();
+            if i > 0 {
+                self.expect(",", "`,`")?;
+                self.skip_ws();
+            }
+            let arg_start = self.pos;
+            let arg = self.argument()?;
+            self.check_argument(arg_start, param, &arg)?;
+            args.push(arg);
+        }
+        self.skip_ws();
+        self.expect(")", "`)`")?;
+        Ok(FunctionCall { function, args })
+    }
+
+    fn argument(&mut self) -> Result<Argument, ParseError> {
+        // a lone literal, query or function call, or else a logical expression.
+        let start = self.pos;
+        if let Ok(comparable) = self.comparable() {
+            let end = self.pos;
+            self.skip_ws();
+            if matches!(self.peek(), Some(',' | ')')) {
+                self.pos = end;
+                return Ok(match comparable {
+                    Comparable::Literal(value) => Argument::Literal(value),
+                    Comparable::Query(query) => Argument::Query(query),
+                    Comparable::Function(call) => Argument::Function(call),
+                });
+            }
+        }
+        self.pos = start;
+        self.logical_or().map(Argument::Logical)
+    }
+
+    fn check_argument(&self, start: usize, param: Type, arg: &Argument) -> Result<(), ParseError> {
+        let ok = match (param, arg) {
+            (Type::Value, Argument::Literal(_)) => true,
+            (Type::Value, Argument::Query(query)) => query.is_singular(),
+            (Type::Value, Argument::Function(call)) => call.function.result() == Type::Value,
+            (Type::Nodes, Argument::Query(_)) => true,
+            (Type::Nodes, Argument::Function(call)) => call.function.result() == Type::Nodes,
+            (Type::Logical, Argument::Logical(_) | Argument::Query(_)) => true,
+            (Type::Logical, Argument::Function(call)) => call.function.result() != Type::Value,
+            _ => false,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(self.error_at(start, ParseErrorKind::Type("invalid function argument")))
+        }
+    }
+}
+
+fn hex4(chars: &mut std::str::CharIndices) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.1.to_digit(16)?;
+    }
+    Some(code)
+}
+
+/// The node itself, under any `Some` the input format wrapped it in.
+fn unwrap<'a, 'de>(node: &'a Content<'de>) -> &'a Content<'de> {
+    match node {
+        Content::Some(node) => unwrap(node),
+        _ => node,
+    }
+}
+
+fn eval_segments<'a, 'de>(
+    segments: &[Segment],
+    root: &'a Content<'de>,
+    mut nodes: Vec<&'a Content<'de>>,
+) -> Result<Vec<&'a Content<'de>>, serde_json::Error> {
+    for segment in segments {
+        let mut next = Vec::new();
+        for node in nodes {
+            match segment {
+                Segment::Child(selectors) => select(selectors, root, node, &mut next)?,
+                Segment::Descendant(selectors) => descend(selectors, root, node, &mut next)?,
+            }
+        }
+        nodes = next;
+    }
+    Ok(nodes)
+}
+
+fn descend<'a, 'de>(
+    selectors: &[Selector],
+    root: &'a Content<'de>,
+    node: &'a Content<'de>,
+    out: &mut Vec<&'a Content<'de>>,
+) -> Result<(), serde_json::Error> {
+    select(selectors, root, node, out)?;
+    match unwrap(node) {
+        Content::Map(entries) => {
+            for (_, value) in entries {
+                descend(selectors, root, value, out)?;
+            }
+        }
+        Content::Seq(elements) => {
+            for element in elements {
+                descend(selectors, root, element, out)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn select<'a, 'de>(
+    selectors: &[Selector],
+    root: &'a Content<'de>,
+    node: &'a Content<'de>,
+    out: &mut Vec<&'a Content<'de>>,
+) -> Result<(), serde_json::Error> {
+    let node = unwrap(node);
+    for selector in selectors {
+        match (selector, node) {
+            (Selector::Name(name), Content::Map(entries)) => {
+                out.extend(
+                    entries
+                        .iter()
+                        .filter(|(k, _)| k.as_str() == Some(name))
+                        .map(|(_, v)| v),
+                );
+            }
+            (Selector::Wildcard, Content::Map(entries)) => {
+                out.extend(entries.iter().map(|(_, v)| v));
+            }
+            (Selector::Wildcard, Content::Seq(elements)) => out.extend(elements),
+            (Selector::Index(index), Content::Seq(elements)) => {
+                let len = elements.len() as i64;
+                let index = if *index < 0 { len + index } else { *index };
+                if (0..len).contains(&index) {
+                    out.push(&elements[index as usize]);
+                }
+            }
+            (Selector::Slice(slice), Content::Seq(elements)) => {
+                out.extend(slice.indices(elements.len()).map(|i| &elements[i]));
+            }
+            (Selector::Filter(filter), Content::Map(entries)) => {
+                for (_, value) in entries {
+                    if eval_logical(filter, root, value)? {
+                        out.push(value);
+                    }
+                }
+            }
+            (Selector::Filter(filter), Content::Seq(elements)) => {
+                for element in elements {
+                    if eval_logical(filter, root, element)? {
+                        out.push(element);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether the filter expression holds for `current`.
+fn eval_logical<'a, 'de>(
+    expr: &Logical,
+    root: &'a Content<'de>,
+    current: &'a Content<'de>,
+) -> Result<bool, serde_json::Error> {
+    Ok(match expr {
+        Logical::Or(operands) => {
+            for operand in operands {
+                if eval_logical(operand, root, current)? {
+                    return Ok(true);
+                }
+            }
+            false
+        }
+        Logical::And(operands) => {
+            for operand in operands {
+                if !eval_logical(operand, root, current)? {
+                    return Ok(false);
+                }
+            }
+            true
+        }
+        Logical::Not(expr) => !eval_logical(expr, root, current)?,
+        Logical::Exists(query) => !eval_query(query, root, current)?.is_empty(),
+        Logical::Function(call) => match eval_function(call, root, current)? {
+            FunctionResult::Logical(b) => b,
+            FunctionResult::Nodes(nodes) => !nodes.is_empty(),
+            FunctionResult::Value(_) => unreachable!("rejected by the parser"),
+        },
+        Logical::Compare(lhs, op, rhs) => {
+            let lhs = eval_comparable(lhs, root, current)?;
+            let rhs = eval_comparable(rhs, root, current)?;
+            compare(lhs.as_ref(), *op, rhs.as_ref())
+        }
+    })
+}
+
+fn eval_query<'a, 'de>(
+    query: &FilterQuery,
+    root: &'a Content<'de>,
+    current: &'a Content<'de>,
+) -> Result<Vec<&'a Content<'de>>, serde_json::Error> {
+    let start = if query.relative { current } else { root };
+    eval_segments(&query.segments, root, vec![start])
+}
+
+/// `None` is the RFC's "Nothing".
+fn eval_comparable(
+    comparable: &Comparable,
+    root: &Content,
+    current: &Content,
+) -> Result<Option<Value>, serde_json::Error> {
+    match comparable {
+        Comparable::Literal(value) => Ok(Some(value.clone())),
+        Comparable::Query(query) => singular_value(eval_query(query, root, current)?),
+        Comparable::Function(call) => match eval_function(call, root, current)? {
+            FunctionResult::Value(value) => Ok(value),
+            _ => unreachable!("rejected by the parser"),
+        },
+    }
+}
+
+fn singular_value(nodes: Vec<&Content>) -> Result<Option<Value>, serde_json::Error> {
+    match nodes[..] {
+        [node] => de::Deserialize::deserialize(node).map(Some),
+        _ => Ok(None),
+    }
+}
+
+enum FunctionResult<'a, 'de> {
+    Value(Option<Value>),
+    Logical(bool),
+    Nodes(Vec<&'a Content<'de>>),
+}
+
+fn eval_function<'a, 'de>(
+    call: &FunctionCall,
+    root: &'a Content<'de>,
+    current: &'a Content<'de>,
+) -> Result<FunctionResult<'a, 'de>, serde_json::Error> {
+    let mut args = Vec::with_capacity(call.args.len());
+    for arg in &call.args {
+        args.push(match arg {
+            Argument::Literal(value) => FunctionResult::Value(Some(value.clone())),
+            Argument::Query(query) => FunctionResult::Nodes(eval_query(query, root, current)?),
+            Argument::Logical(expr) => FunctionResult::Logical(eval_logical(expr, root, current)?),
+            Argument::Function(call) => eval_function(call, root, current)?,
+        });
+    }
+
+    let value = |arg: FunctionResult<'a, 'de>| match arg {
+        FunctionResult::Value(value) => Ok(value),
+        FunctionResult::Nodes(nodes) => singular_value(nodes),
+        FunctionResult::Logical(_) => unreachable!("rejected by the parser"),
+    };
+    let nodes = |arg: FunctionResult<'a, 'de>| match arg {
+        FunctionResult::Nodes(nodes) => nodes,
+        _ => unreachable!("rejected by the parser"),
+    };
+
+    let mut args = args.into_iter();
+    let mut arg = || args.next().expect("arity is checked by the parser");
+    Ok(match call.function {
+        Function::Length => FunctionResult::Value(match value(arg())? {
+            Some(Value::String(s)) => Some(s.chars().count().into()),
+            Some(Value::Array(a)) => Some(a.len().into()),
+            Some(Value::Object(o)) => Some(o.len().into()),
+            _ => None,
+        }),
+        Function::Count => FunctionResult::Value(Some(nodes(arg()).len().into())),
+        Function::Value => FunctionResult::Value(singular_value(nodes(arg()))?),
+        #[cfg(feature = "regex")]
+        Function::Match | Function::Search => {
+            let full = call.function == Function::Match;
+            FunctionResult::Logical(match (value(arg())?, value(arg())?) {
+                (Some(Value::String(s)), Some(Value::String(pattern))) => {
+                    iregexp(&pattern, full).is_some_and(|regex| regex.is_match(&s))
+                }
+                _ => false,
+            })
+        }
+        #[cfg(not(feature = "regex"))]
+        Function::Match | Function::Search => unreachable!("rejected by the parser"),
+    })
+}
+
+/// Compiles an RFC 9485 I-Regexp, anchored at both ends if `full`. `None` if it is invalid.
+#[cfg(feature = "regex")]
+fn iregexp(pattern: &str, full: bool) -> Option<regex::Regex> {
+    let mut translated = String::with_capacity(pattern.len() + 8);
+    if full {
+        translated.push_str(r"\A(?:");
+    }
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                translated.push(c);
+                translated.extend(chars.next());
+            }
+            // `.` does not match line breaks of either kind
+            '.' if !in_class => translated.push_str(r"[^\n\r]"),
+            _ => {
+                in_class = match c {
+                    '[' => true,
+                    ']' => false,
+                    _ => in_class,
+                };
+                translated.push(c);
+            }
+        }
+    }
+    if full {
+        translated.push_str(r")\z");
+    }
+    regex::Regex::new(&translated).ok()
+}
+
+fn compare(lhs: Option<&Value>, op: CompareOp, rhs: Option<&Value>) -> bool {
+    match op {
+        CompareOp::Eq => equal(lhs, rhs),
+        CompareOp::Ne => !equal(lhs, rhs),
+        CompareOp::Lt => less(lhs, rhs),
+        CompareOp::Le => less(lhs, rhs) || equal(lhs, rhs),
+        CompareOp::Gt => less(rhs, lhs),
+        CompareOp::Ge => less(rhs, lhs) || equal(lhs, rhs),
+    }
+}
+
+fn equal(lhs: Option<&Value>, rhs: Option<&Value>) -> bool {
+    match (lhs, rhs) {
+        (None, None) => true,
+        (Some(lhs), Some(rhs)) => values_equal(lhs, rhs),
+        _ => false,
+    }
+}
+
+fn values_equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b) == Some(Ordering::Equal),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, a)| b.get(k).is_some_and(|b| values_equal(a, b)))
+        }
+        _ => lhs == rhs,
+    }
+}
+
+fn less(lhs: Option<&Value>, rhs: Option<&Value>) -> bool {
+    match (lhs, rhs) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => {
+            compare_numbers(a, b) == Some(Ordering::Less)
+        }
+        (Some(Value::String(a)), Some(Value::String(b))) => a < b,
+        _ => false,
+    }
+}
+
+fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    let int = |n: &Number| n.as_i64().map(i128::from).or(n.as_u64().map(i128::from));
+    match (int(a), int(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde::Deserialize;
+    use serde_json::{Value, json, value::RawValue};
+
+    use crate::{
+        FilterChain,
+        json_ser::JsonSer,
+        jsonpath::{ParseError, ParseErrorKind, parse},
+    };
+
+    /// Test cases in the schema of the JSONPath Compliance Test Suite's `cts.json`.
+    ///
+    /// See `tests/fixtures/README.md` for where the cases come from, and how to replace them
+    /// with the upstream `cts.json`.
+    #[derive(Deserialize)]
+    struct TestSuite {
+        tests: Vec<TestCase>,
+    }
+
+    #[derive(Deserialize)]
+    struct TestCase {
+        name: String,
+        selector: String,
+        document: Option<Box<RawValue>>,
+        #[serde(default)]
+        result: Option<Vec<Value>>,
+        #[serde(default)]
+        results: Option<Vec<Vec<Value>>>,
+        #[serde(default)]
+        invalid_selector: bool,
+    }
+
+    /// Cases of the suite that are not supported, by name, with the reason.
+    const SKIP: &[(&str, &str)] = &[];
+
+    #[test]
+    fn compliance() {
+        let suite: TestSuite =
+            serde_json::from_str(include_str!("../tests/fixtures/jsonpath-cts.json")).unwrap();
+
+        let mut skipped = Vec::new();
+        for case in suite.tests {
+            let TestCase {
+                name,
+                selector,
+                document,
+                result,
+                results,
+                invalid_selector,
+            } = case;
+            if SKIP.iter().any(|(skip, _)| *skip == name) {
+                skipped.push(name);
+                continue;
+            }
+            let query = parse(&selector);
+            #[cfg(not(feature = "regex"))]
+            if let Err(err) = &query
+                && err.kind() == &ParseErrorKind::Unsupported("regular expressions")
+            {
+                continue;
+            }
+            if invalid_selector {
+                assert!(query.is_err(), "{name}: `{selector}` should be invalid");
+                continue;
+            }
+            let query = query.unwrap_or_else(|err| panic!("{name}: `{selector}`: {err}"));
+
+            let document = document.unwrap_or_else(|| panic!("{name}: no document"));
+            let actual: Vec<Value> = query
+                .filter(
+                    PhantomData,
+                    &mut serde_json::Deserializer::from_str(document.get()),
+                )
+                .unwrap_or_else(|err| panic!("{name}: {err}"));
+
+            match (result, results) {
+                (Some(expected), _) => assert_eq!(actual, expected, "{name}"),
+                (_, Some(expected)) => assert!(expected.contains(&actual), "{name}: {actual:?}"),
+                (None, None) => panic!("{name}: no expected result"),
+            }
+        }
+
+        for (name, _) in SKIP {
+            assert!(
+                skipped.contains(&name.to_string()),
+                "no case named {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let json = json!({"a": [{"b": 1}, {"b": 2}, {"c": 3}]}).to_string();
+        let query = parse("$.a[?@.b > 1, 2]").unwrap();
+
+        let ser = serde_json::Serializer::new(Vec::new());
+        let output = query
+            .filter(JsonSer(ser), &mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), r#"[{"b":2},{"c":3}]"#);
+    }
+
+    #[test]
+    fn formats() {
+        let json = json!({"a": {"b": [{"c": 1}, {"c": 2}], "n": 1}}).to_string();
+        let query = parse("$.a.b[?@.c > 1].c").unwrap();
+
+        let reader = serde_json::Deserializer::from_reader(json.as_bytes());
+        let v: Vec<u32> = (&query).filter(PhantomData, &mut { reader }).unwrap();
+        assert_eq!(v, [2]);
+        let v: Vec<u32> = (&query)
+            .filter(PhantomData, serde_yaml::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(v, [2]);
+
+        // the root is still the whole input
+        let query = parse("$.a.b[?@.c == $.a.n].c").unwrap();
+        let v: Vec<u32> = query
+            .filter(PhantomData, serde_yaml::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(v, [1]);
+        let v: Vec<u32> = parse("$.a.x[0]")
+            .unwrap()
+            .filter(PhantomData, serde_yaml::Deserializer::from_str(&json))
+            .unwrap();
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn regex() {
+        let json = json!([
+            {"date": "1974-05-01", "author": "Bob"},
+            {"date": "1974-05-10", "author": "Rob"},
+            {"date": "1974-06-01", "author": "Alice"},
+            {"date": "1974-05-0\n", "author": "Carol"},
+        ])
+        .to_string();
+        let query = |query: &str| -> Result<Vec<Value>, ParseError> {
+            let query = parse(query)?;
+            Ok(query
+                .filter(PhantomData, &mut serde_json::Deserializer::from_str(&json))
+                .unwrap())
+        };
+
+        #[cfg(feature = "regex")]
+        {
+            let dates = query(r#"$[?match(@.date, "1974-05-..")].author"#).unwrap();
+            assert_eq!(dates, [json!("Bob"), json!("Rob")]);
+            let authors = query(r#"$[?search(@.author, "[BR]ob")].date"#).unwrap();
+            assert_eq!(authors, [json!("1974-05-01"), json!("1974-05-10")]);
+            assert_eq!(query(r#"$[?match(@.author, "ob")]"#).unwrap().len(), 0);
+            // an invalid regex does not match anything
+            assert_eq!(query(r#"$[?search(@.author, "(")]"#).unwrap().len(), 0);
+        }
+        #[cfg(not(feature = "regex"))]
+        assert_eq!(
+            query(r#"$[?match(@.date, "1974-05-..")]"#)
+                .unwrap_err()
+                .kind(),
+            &ParseErrorKind::Unsupported("regular expressions")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = parse("$.a[?@.b ==]").unwrap_err();
+        assert_eq!(err.offset(), 11);
+        assert_eq!(
+            err.to_string(),
+            "expected a query, literal or function at offset 11"
+        );
+
+        let err = parse("$[?@.* == 1]").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ParseErrorKind::Type("only singular queries can be compared")
+        );
+        assert_eq!(err.offset(), 3);
+
+        let err = parse("$[?foo(@)]").unwrap_err();
+        assert_eq!(err.kind(), &ParseErrorKind::UnknownFunction("foo".into()));
+    }
+}
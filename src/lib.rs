@@ -1,23 +1,76 @@
 mod borrow;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod content;
+pub mod jq;
 pub mod json;
 pub mod json_ser;
+pub mod jsonpath;
 mod list;
 pub mod map;
+pub mod map_select;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
 mod multi;
 mod obj;
+pub mod optional;
+pub mod pointer;
 pub mod predicate;
 pub mod raw;
 pub mod select;
+pub mod stream;
+pub mod track;
 
+pub use list::{FromEnd, Slice};
 pub use multi::{MultiMap, MultiVec};
+/// ```compile_fail
+/// let filter = serde_path::path!(.a..b);
+/// ```
+pub use serde_path_macros::path;
 
-use serde::de;
+extern crate self as serde_path;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+}
+
+use serde::de::{self, value::UnitDeserializer};
 
 pub trait FilterChain<'de> {
     fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
     where
         D: de::Deserializer<'de>,
         S: de::DeserializeSeed<'de>;
+
+    /// Like [`FilterChain::filter`], but returns `None` without using the seed when an
+    /// [`Optional`](optional::Optional) filter found nothing.
+    fn filter_opt<D, S>(self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        Self: Sized,
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        self.filter(seed, deserializer).map(Some)
+    }
+}
+
+/// Runs the filter, passing `null` to the seed if it found nothing.
+pub(crate) fn or_null<'de, F, D, S>(
+    filter: F,
+    seed: S,
+    deserializer: D,
+) -> Result<S::Value, D::Error>
+where
+    F: FilterChain<'de>,
+    D: de::Deserializer<'de>,
+    S: de::DeserializeSeed<'de>,
+{
+    let mut seed = TakeWrapper(Some(seed));
+    match filter.filter_opt(&mut seed, deserializer)? {
+        Some(val) => Ok(val),
+        None => seed.0.take().unwrap().deserialize(UnitDeserializer::new()),
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -62,6 +115,16 @@ where
         let (head, filter) = self;
         head.filter(Chain { filter, seed }, deserializer)
     }
+
+    fn filter_opt<D, S>(self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let (head, filter) = self;
+        head.filter_opt(ChainOpt { filter, seed }, deserializer)
+            .map(Option::flatten)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -81,6 +144,19 @@ where
             Some(head) => head.filter(Chain { filter: self, seed }, deserializer),
         }
     }
+
+    fn filter_opt<D, S>(mut self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        match self.0.next() {
+            None => Final.filter_opt(seed, deserializer),
+            Some(head) => head
+                .filter_opt(ChainOpt { filter: self, seed }, deserializer)
+                .map(Option::flatten),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -105,6 +181,28 @@ where
     }
 }
 
+/// [`Chain`] for [`FilterChain::filter_opt`].
+pub(crate) struct ChainOpt<F, S> {
+    pub(crate) filter: F,
+    pub(crate) seed: S,
+}
+
+impl<'de, F, S> de::DeserializeSeed<'de> for ChainOpt<F, S>
+where
+    F: FilterChain<'de>,
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = Option<S::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let Self { filter, seed } = self;
+        filter.filter_opt(seed, deserializer)
+    }
+}
+
 struct TakeWrapper<S>(Option<S>);
 impl<'de, S: de::DeserializeSeed<'de>> de::DeserializeSeed<'de> for &mut TakeWrapper<S> {
     type Value = S::Value;
@@ -150,4 +248,12 @@ mod tests {
         let field: &RawValue = extract_json_path(&json, hlist!["b", "c", 1]).unwrap();
         assert_eq!(field.get(), "3")
     }
+
+    #[test]
+    fn non_clone_tail() {
+        fn is_chain<'de, F: FilterChain<'de>>(_: F) {}
+
+        let mut de = serde_json::Deserializer::from_str("5");
+        is_chain(hlist!["a", crate::Const(&mut de)]);
+    }
 }
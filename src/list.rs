@@ -1,6 +1,12 @@
-use serde::de;
+use std::{cmp::Ordering, collections::VecDeque, num::NonZeroUsize};
 
-use crate::FilterChain;
+use serde::de::{self, value::SeqAccessDeserializer};
+
+use crate::{
+    FilterChain,
+    raw::RawValue2,
+    track::{self, Segment},
+};
 
 impl<'de> FilterChain<'de> for usize {
     fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
@@ -15,9 +21,9 @@ impl<'de> FilterChain<'de> for usize {
     }
 }
 
-struct ListVisitor<S> {
-    head: usize,
-    next: S,
+pub(crate) struct ListVisitor<S> {
+    pub(crate) head: usize,
+    pub(crate) next: S,
 }
 
 impl<'de, S> de::Visitor<'de> for ListVisitor<S>
@@ -35,7 +41,7 @@ where
         let mut i = 0;
         let v = loop {
             if i == head {
-                break seq.next_element_seed(next)?;
+                break track::at(seq.next_element_seed(next), || Segment::Index(head))?;
             }
 
             if seq.next_element::<de::IgnoredAny>()?.is_none() {
@@ -50,3 +56,331 @@ where
         Ok(v)
     }
 }
+
+/// Counts from the back of the sequence, `FromEnd::new(1)` being the last element.
+///
+/// Only the last `n` elements are kept while the sequence is read.
+#[derive(Clone, Copy, Debug)]
+pub struct FromEnd(pub NonZeroUsize);
+
+impl FromEnd {
+    /// `None` for 0, which is not counted from the end.
+    pub const fn new(n: usize) -> Option<Self> {
+        match NonZeroUsize::new(n) {
+            Some(n) => Some(Self(n)),
+            None => None,
+        }
+    }
+}
+
+impl<'de> FilterChain<'de> for FromEnd {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        deserializer.deserialize_seq(FromEndVisitor {
+            back: self.0.get(),
+            next: seed,
+        })
+    }
+}
+
+struct FromEndVisitor<S> {
+    back: usize,
+    next: S,
+}
+
+impl<'de, S> de::Visitor<'de> for FromEndVisitor<S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a sequence containing element -{:?}", self.back)
+    }
+
+    #[inline]
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let Self { back, next } = self;
+        let mut ring = VecDeque::new();
+        while let Some(v) = seq.next_element::<RawValue2<'de>>()? {
+            if ring.len() == back {
+                ring.pop_front();
+            }
+            ring.push_back(v);
+        }
+
+        if ring.len() < back {
+            return Err(de::Error::custom(format_args!("missing field `-{back:?}`")));
+        }
+        track::at(ring[0].deserialize_into(next), || Segment::FromEnd(back))
+            .map_err(de::Error::custom)
+    }
+}
+
+/// `[start:end:step]`, yielding the selected elements as a sequence.
+///
+/// Negative bounds count from the end of the sequence, which then has to be buffered.
+/// With non-negative bounds and a positive step, elements are streamed into the seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub start: Option<isize>,
+    pub end: Option<isize>,
+    pub step: isize,
+}
+
+impl Default for Slice {
+    fn default() -> Self {
+        Self {
+            start: None,
+            end: None,
+            step: 1,
+        }
+    }
+}
+
+impl Slice {
+    /// The selected indices of a sequence with `len` elements, in output order.
+    pub(crate) fn indices(self, len: usize) -> impl Iterator<Item = usize> {
+        let len = len as isize;
+        let step = self.step;
+        let normalize = |i: isize| if i >= 0 { i } else { len.saturating_add(i) };
+
+        let (mut i, bound) = match step.cmp(&0) {
+            Ordering::Greater => (
+                self.start.map_or(0, normalize).clamp(0, len),
+                self.end.map_or(len, normalize).clamp(0, len),
+            ),
+            Ordering::Less => (
+                self.start.map_or(len - 1, normalize).clamp(-1, len - 1),
+                self.end.map_or(-1, normalize).clamp(-1, len - 1),
+            ),
+            Ordering::Equal => (0, 0),
+        };
+
+        std::iter::from_fn(move || {
+            let in_range = match step.cmp(&0) {
+                Ordering::Greater => i < bound,
+                Ordering::Less => bound < i,
+                Ordering::Equal => false,
+            };
+            if !in_range {
+                return None;
+            }
+            let index = i as usize;
+            i = i.saturating_add(step);
+            Some(index)
+        })
+    }
+
+    fn streaming(self) -> bool {
+        self.step > 0 && self.start.unwrap_or(0) >= 0 && self.end.unwrap_or(0) >= 0
+    }
+}
+
+impl<'de> FilterChain<'de> for Slice {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        deserializer.deserialize_seq(SliceVisitor {
+            slice: self,
+            next: seed,
+        })
+    }
+}
+
+struct SliceVisitor<S> {
+    slice: Slice,
+    next: S,
+}
+
+impl<'de, S> de::Visitor<'de> for SliceVisitor<S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a sequence")
+    }
+
+    #[inline]
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let Self { slice, next } = self;
+
+        if !slice.streaming() {
+            let mut elements = Vec::new();
+            while let Some(v) = seq.next_element::<RawValue2<'de>>()? {
+                elements.push(v);
+            }
+            let selected = slice.indices(elements.len()).map(|i| &elements[i]);
+            return next
+                .deserialize(SeqAccessDeserializer::new(BufferedSeqAccess(selected)))
+                .map_err(de::Error::custom);
+        }
+
+        let v = next
+            .deserialize(SeqAccessDeserializer::new(SliceSeqAccess {
+                seq: &mut seq,
+                index: 0,
+                next: slice.start.unwrap_or(0) as usize,
+                end: slice.end.map(|end| end as usize),
+                step: slice.step as usize,
+                done: false,
+            }))
+            .map_err(de::Error::custom)?;
+
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+        Ok(v)
+    }
+}
+
+/// Buffered elements, passed on without decoding them again.
+struct BufferedSeqAccess<I>(I);
+
+impl<'a, 'de: 'a, I> de::SeqAccess<'de> for BufferedSeqAccess<I>
+where
+    I: Iterator<Item = &'a RawValue2<'de>>,
+{
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.0.next().map(|v| v.deserialize_into(seed)).transpose()
+    }
+}
+
+struct SliceSeqAccess<'a, A> {
+    seq: &'a mut A,
+    /// index of the next element in `seq`.
+    index: usize,
+    /// index of the next element to yield.
+    next: usize,
+    end: Option<usize>,
+    step: usize,
+    done: bool,
+}
+
+impl<'de, A> de::SeqAccess<'de> for SliceSeqAccess<'_, A>
+where
+    A: de::SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.done || self.end.is_some_and(|end| self.next >= end) {
+            return Ok(None);
+        }
+
+        while self.index < self.next {
+            if self.seq.next_element::<de::IgnoredAny>()?.is_none() {
+                self.done = true;
+                return Ok(None);
+            }
+            self.index += 1;
+        }
+
+        let index = self.index;
+        let v = track::at(self.seq.next_element_seed(seed), || Segment::Index(index));
+        let Some(v) = v? else {
+            self.done = true;
+            return Ok(None);
+        };
+        self.index += 1;
+        self.next = self.next.saturating_add(self.step);
+        Ok(Some(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::json;
+
+    use super::{FromEnd, Slice};
+    use crate::{FilterChain, hlist};
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn from_end() {
+        let json = json!({ "a": 1, "b": {"c": [2, 3, 4]}, "d": 5}).to_string();
+        let field: u32 =
+            extract_json_path(&json, hlist!["b", "c", FromEnd::new(1).unwrap()]).unwrap();
+        assert_eq!(field, 4);
+        let field: u32 =
+            extract_json_path(&json, hlist!["b", "c", FromEnd::new(3).unwrap()]).unwrap();
+        assert_eq!(field, 2);
+
+        let err = extract_json_path::<_, u32>(&json, hlist!["b", "c", FromEnd::new(4).unwrap()])
+            .unwrap_err();
+        assert!(err.to_string().contains("missing field `-4`"), "{err}");
+        assert!(FromEnd::new(0).is_none());
+    }
+
+    #[test]
+    fn slice() {
+        let json = json!({ "a": [0, 1, 2, 3, 4, 5, 6] }).to_string();
+        let slice = |start, end, step| hlist!["a", Slice { start, end, step }];
+
+        let v: Vec<u32> = extract_json_path(&json, slice(Some(2), Some(5), 1)).unwrap();
+        assert_eq!(v, [2, 3, 4]);
+        let v: Vec<u32> = extract_json_path(&json, slice(None, None, 3)).unwrap();
+        assert_eq!(v, [0, 3, 6]);
+        let v: Vec<u32> = extract_json_path(&json, slice(Some(5), Some(100), 1)).unwrap();
+        assert_eq!(v, [5, 6]);
+        let v: Vec<u32> = extract_json_path(&json, slice(Some(-2), None, 1)).unwrap();
+        assert_eq!(v, [5, 6]);
+        let v: Vec<u32> = extract_json_path(&json, slice(Some(1), Some(-4), 1)).unwrap();
+        assert_eq!(v, [1, 2]);
+        let v: Vec<u32> = extract_json_path(&json, slice(None, None, -2)).unwrap();
+        assert_eq!(v, [6, 4, 2, 0]);
+        let v: Vec<u32> = extract_json_path(&json, slice(Some(4), Some(1), 1)).unwrap();
+        assert!(v.is_empty());
+        let v: Vec<u32> = extract_json_path(&json, slice(None, None, 0)).unwrap();
+        assert!(v.is_empty());
+
+        // a seed that stops early still leaves the deserializer after the array.
+        let (first, second): (u32, u32) =
+            extract_json_path(&json, slice(Some(3), None, 1)).unwrap();
+        assert_eq!((first, second), (3, 4));
+    }
+
+    #[test]
+    fn slice_streaming() {
+        // streaming does not need to borrow raw values from the input.
+        let json = json!({ "a": [0, 1, 2, 3, 4, 5, 6], "b": 7 }).to_string();
+        let filter = hlist![
+            "a",
+            Slice {
+                start: Some(1),
+                end: Some(4),
+                step: 2
+            }
+        ];
+        let v: Vec<u32> = filter
+            .filter(
+                PhantomData::<Vec<u32>>,
+                &mut serde_json::Deserializer::from_reader(json.as_bytes()),
+            )
+            .unwrap();
+        assert_eq!(v, [1, 3]);
+    }
+}
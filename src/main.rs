@@ -0,0 +1,620 @@
+//! `serde-path`: runs a jq filter over every JSON value of files or stdin.
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use serde_json::{
+    ser::{Formatter, PrettyFormatter},
+    value::RawValue,
+};
+use serde_path::{
+    jq,
+    json::JsonPath,
+    stream::{Records, Writer},
+};
+
+const USAGE: &str = "\
+Usage: serde-path [OPTIONS] FILTER [FILES...]
+
+Runs a jq filter over every JSON value of the files, or of stdin.
+
+Options:
+  -c, --compact-output  write each output on one line instead of pretty-printing it
+  -r, --raw-output      write strings without quotes or escapes
+  -n, --null-input      run the filter once on `null` instead of reading any input
+  -s, --slurp           run the filter once on an array of all the input values
+  -e, --exit-status     exit with 1 if the last output was `false` or `null`, or 4 if
+                        there was none
+  -h, --help            print this message
+";
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    compact: bool,
+    raw: bool,
+    null_input: bool,
+    slurp: bool,
+    exit_status: bool,
+    filter: String,
+    files: Vec<PathBuf>,
+}
+
+/// Returns `None` if help was asked for.
+fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Option<Args>, String> {
+    let mut parsed = Args::default();
+    let mut filter = None;
+    let mut options = true;
+    for arg in args {
+        let flags = match arg.to_str() {
+            Some("--") if options => {
+                options = false;
+                continue;
+            }
+            Some(long) if options && long.starts_with("--") => match long {
+                "--compact-output" => vec!['c'],
+                "--raw-output" => vec!['r'],
+                "--null-input" => vec!['n'],
+                "--slurp" => vec!['s'],
+                "--exit-status" => vec!['e'],
+                "--help" => vec!['h'],
+                _ => return Err(format!("unknown option `{long}`")),
+            },
+            Some(short) if options && short.len() > 1 && short.starts_with('-') => {
+                short[1..].chars().collect()
+            }
+            _ if filter.is_none() => {
+                let arg = arg
+                    .into_string()
+                    .map_err(|_| "the filter is not valid UTF-8".to_string())?;
+                filter = Some(arg);
+                continue;
+            }
+            _ => {
+                parsed.files.push(arg.into());
+                continue;
+            }
+        };
+        for flag in flags {
+            match flag {
+                'c' => parsed.compact = true,
+                'r' => parsed.raw = true,
+                'n' => parsed.null_input = true,
+                's' => parsed.slurp = true,
+                'e' => parsed.exit_status = true,
+                'h' => return Ok(None),
+                _ => return Err(format!("unknown option `-{flag}`")),
+            }
+        }
+    }
+    parsed.filter = filter.ok_or("missing the filter")?;
+    Ok(Some(parsed))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args_os().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("serde-path: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let (filter, stream) = match jq::parse_program(&args.filter) {
+        Ok(filter) => filter,
+        Err(err) => {
+            eprintln!("serde-path: {err}");
+            return ExitCode::from(3);
+        }
+    };
+
+    let stdin_only = args.files.is_empty();
+    let inputs = args
+        .files
+        .iter()
+        .map(|path| (path.display().to_string(), open(path)))
+        .chain(stdin_only.then(|| ("<stdin>".to_string(), open(Path::new("-")))));
+
+    let stdout = BufWriter::new(io::stdout().lock());
+    let code = if args.compact {
+        run(&args, filter, inputs, Writer::new(stdout).stream(stream))
+    } else {
+        let writer = Writer::with_formatter(stdout, PrettyFormatter::new()).stream(stream);
+        run(&args, filter, inputs, writer)
+    };
+    ExitCode::from(code)
+}
+
+fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    let file = File::open(path)?;
+    Ok(Box::new(BufReader::with_capacity(1 << 16, file)))
+}
+
+/// Runs the filter over the inputs, returning the exit code: 2 if an input could not be read,
+/// 5 if the filter failed, and otherwise the status asked for by `-e`.
+fn run<I, R, W, Fmt>(args: &Args, filter: JsonPath, inputs: I, writer: Writer<W, Fmt>) -> u8
+where
+    I: IntoIterator<Item = (String, io::Result<R>)>,
+    R: BufRead,
+    W: Write,
+    Fmt: Formatter + Clone,
+{
+    let mut writer = writer.raw(args.raw).keep_output(args.exit_status);
+    let mut status = Status::default();
+    let result = if args.null_input {
+        let written = status.write(&mut writer, &filter, RawValue::NULL);
+        written.or_else(|err| status.failed("<null>", 0, err))
+    } else if args.slurp {
+        slurp(inputs, &mut status).and_then(|all| match all {
+            Some(all) => {
+                let written = status.write(&mut writer, &filter, &*all);
+                written.or_else(|err| status.failed("<slurp>", 0, err))
+            }
+            None => Ok(()),
+        })
+    } else {
+        inputs
+            .into_iter()
+            .try_for_each(|(name, reader)| match reader {
+                Ok(reader) => status.write_each(&mut writer, &filter, &name, reader),
+                Err(err) => {
+                    status.read_error(&name, err);
+                    Ok(())
+                }
+            })
+    };
+    match result.and_then(|()| writer.flush()) {
+        // the reader of the output has gone, such as `head`
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("serde-path: writing the output: {err}");
+            return 2;
+        }
+        Ok(()) => {}
+    }
+    status.code(args.exit_status)
+}
+
+/// Reads every input value into one array, or returns `None` if one could not be read.
+fn slurp<I, R>(inputs: I, status: &mut Status) -> io::Result<Option<Box<RawValue>>>
+where
+    I: IntoIterator<Item = (String, io::Result<R>)>,
+    R: BufRead,
+{
+    let mut all = String::from("[");
+    for (name, reader) in inputs {
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(err) => {
+                status.read_error(&name, err);
+                return Ok(None);
+            }
+        };
+        for (index, raw) in Records::new(reader).enumerate() {
+            match raw {
+                Ok(raw) => {
+                    if all.len() > 1 {
+                        all.push(',');
+                    }
+                    all.push_str(raw.get());
+                }
+                Err(err) => {
+                    status.read_error(&format!("{name}: value {index}"), err);
+                    return Ok(None);
+                }
+            }
+        }
+    }
+    all.push(']');
+    Ok(Some(RawValue::from_string(all)?))
+}
+
+#[derive(Debug, Default)]
+struct Status {
+    /// Whether the last output was neither `false` nor `null`, if there was one.
+    last: Option<bool>,
+    read_failed: bool,
+    filter_failed: bool,
+}
+
+impl Status {
+    /// Writes the output for one value.
+    fn write<'de, W, Fmt, D>(
+        &mut self,
+        writer: &mut Writer<W, Fmt>,
+        filter: &JsonPath,
+        value: D,
+    ) -> Result<(), serde_json::Error>
+    where
+        W: Write,
+        Fmt: Formatter + Clone,
+        D: serde::Deserializer<'de, Error = serde_json::Error>,
+    {
+        if writer.write(filter.clone(), value)? {
+            self.last = Some(!matches!(writer.output(), b"false" | b"null"));
+        }
+        Ok(())
+    }
+
+    /// Writes the output for every value of an input, reading each one as it is filtered.
+    /// Only I/O errors writing the output are returned.
+    fn write_each<R, W, Fmt>(
+        &mut self,
+        writer: &mut Writer<W, Fmt>,
+        filter: &JsonPath,
+        name: &str,
+        reader: R,
+    ) -> io::Result<()>
+    where
+        R: BufRead,
+        W: Write,
+        Fmt: Formatter + Clone,
+    {
+        let mut reader = Nesting::new(reader);
+        for index in 0.. {
+            let number = match reader.start() {
+                Ok(Some(first)) => first == b'-' || first.is_ascii_digit(),
+                Ok(None) => break,
+                Err(err) => {
+                    self.read_error(name, err);
+                    break;
+                }
+            };
+            let mut de = serde_json::Deserializer::from_reader(&mut reader);
+            let written = self.write(writer, filter, &mut de);
+            if number {
+                reader.unread_after_number();
+            }
+            let Err(err) = written else {
+                continue;
+            };
+            if let Some(err) = reader.error.take() {
+                self.read_error(name, err);
+                break;
+            }
+            let unreadable = err.is_syntax() || err.is_eof();
+            self.failed(name, index, err)?;
+            if unreadable {
+                break;
+            }
+            if let Err(err) = reader.skip_value() {
+                self.read_error(name, err);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports a value that could not be read or filtered. Only I/O errors writing the output
+    /// are returned.
+    fn failed(&mut self, name: &str, index: usize, err: serde_json::Error) -> io::Result<()> {
+        if err.is_io() {
+            return Err(err.into());
+        }
+        if err.is_syntax() || err.is_eof() {
+            self.read_error(&format!("{name}: value {index}"), err);
+        } else {
+            eprintln!("serde-path: {name}: value {index}: {err}");
+            self.filter_failed = true;
+        }
+        Ok(())
+    }
+
+    fn read_error(&mut self, name: &str, err: impl std::fmt::Display) {
+        eprintln!("serde-path: {name}: {err}");
+        self.read_failed = true;
+    }
+
+    fn code(&self, exit_status: bool) -> u8 {
+        match self.last {
+            _ if self.read_failed => 2,
+            _ if self.filter_failed => 5,
+            _ if !exit_status => 0,
+            Some(true) => 0,
+            Some(false) => 1,
+            None => 4,
+        }
+    }
+}
+
+/// A reader that follows how deeply nested the JSON read through it is, so that the rest of
+/// a value the filter failed on can be skipped.
+struct Nesting<R> {
+    inner: R,
+    state: State,
+    /// the last byte read, and the state before it.
+    last: Option<(u8, State)>,
+    /// a byte given back by [`Nesting::unread_after_number`].
+    unread: Option<u8>,
+    /// an error reading the input, rather than writing the output.
+    error: Option<io::Error>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct State {
+    depth: usize,
+    string: bool,
+    escape: bool,
+}
+
+impl State {
+    fn track(&mut self, byte: u8) {
+        match byte {
+            _ if self.escape => self.escape = false,
+            b'\\' if self.string => self.escape = true,
+            b'"' => self.string = !self.string,
+            _ if self.string => {}
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn inside(&self) -> bool {
+        self.depth > 0 || self.string
+    }
+}
+
+impl<R: BufRead> Nesting<R> {
+    fn new(inner: R) -> Self {
+        Nesting {
+            inner,
+            state: State::default(),
+            last: None,
+            unread: None,
+            error: None,
+        }
+    }
+
+    /// Skips the whitespace before the next value and returns its first byte, or `None` at
+    /// the end of the input.
+    fn start(&mut self) -> io::Result<Option<u8>> {
+        let is_ws = |byte: &u8| matches!(byte, b' ' | b'\n' | b'\t' | b'\r');
+        match self.unread {
+            Some(byte) if !is_ws(&byte) => return Ok(Some(byte)),
+            _ => self.unread = None,
+        }
+        loop {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            match buf.iter().position(|byte| !is_ws(byte)) {
+                Some(i) => {
+                    let first = buf[i];
+                    self.inner.consume(i);
+                    return Ok(Some(first));
+                }
+                None => {
+                    let len = buf.len();
+                    self.inner.consume(len);
+                }
+            }
+        }
+    }
+
+    /// Gives back the byte after a number, which the parser reads to find where it ends.
+    fn unread_after_number(&mut self) {
+        if let Some((byte, state)) = self.last.take()
+            && !matches!(byte, b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-')
+        {
+            self.state = state;
+            self.unread = Some(byte);
+        }
+    }
+
+    /// Reads the rest of the value being read.
+    fn skip_value(&mut self) -> io::Result<()> {
+        while self.state.inside() {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let mut len = 0;
+            for &byte in buf {
+                len += 1;
+                self.state.track(byte);
+                if !self.state.inside() {
+                    break;
+                }
+            }
+            self.inner.consume(len);
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, byte: u8) {
+        self.last = Some((byte, self.state));
+        self.state.track(byte);
+    }
+}
+
+impl<R: BufRead> Read for Nesting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(byte) = self.unread.take() {
+            buf[0] = byte;
+            self.record(byte);
+            return Ok(1);
+        }
+        let len = self.inner.read(buf).map_err(|err| {
+            if err.kind() == io::ErrorKind::Interrupted {
+                return err;
+            }
+            let copy = io::Error::new(err.kind(), err.to_string());
+            self.error.get_or_insert(err);
+            copy
+        })?;
+        for &byte in &buf[..len] {
+            self.record(byte);
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use serde_json::ser::PrettyFormatter;
+    use serde_path::{jq, stream::Writer};
+
+    use super::{Args, parse_args, run};
+
+    fn args(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(Into::into))
+    }
+
+    /// Runs the filter over the input, returning the exit code and the output.
+    fn output(args: Args, input: &str) -> (u8, String) {
+        let (filter, stream) = jq::parse_program(&args.filter).unwrap();
+        let inputs = [("input".to_string(), Ok(input.as_bytes()))];
+        let mut out = Vec::new();
+        let code = if args.compact {
+            run(&args, filter, inputs, Writer::new(&mut out).stream(stream))
+        } else {
+            let writer = Writer::with_formatter(&mut out, PrettyFormatter::new()).stream(stream);
+            run(&args, filter, inputs, writer)
+        };
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            args(&["-cr", "--slurp", ".a", "a.json", "-", "-e"]).unwrap(),
+            Some(Args {
+                compact: true,
+                raw: true,
+                slurp: true,
+                exit_status: true,
+                filter: ".a".to_string(),
+                files: vec!["a.json".into(), "-".into()],
+                ..Args::default()
+            })
+        );
+        assert_eq!(
+            args(&["-n", "--", "-1", "-c"]).unwrap(),
+            Some(Args {
+                null_input: true,
+                filter: "-1".to_string(),
+                files: vec!["-c".into()],
+                ..Args::default()
+            })
+        );
+        assert_eq!(args(&[".", "-h"]).unwrap(), None);
+        assert!(args(&["-x", "."]).is_err());
+        assert!(args(&["--compact"]).is_err());
+        assert!(args(&["-c"]).is_err());
+    }
+
+    #[test]
+    fn outputs() {
+        let input = "{\"a\": [1, \"x\\ty\"]}\n{\"a\": null}\n";
+        let compact = |filter: &str| Args {
+            compact: true,
+            filter: filter.to_string(),
+            ..Args::default()
+        };
+
+        assert_eq!(
+            output(compact(".a"), input),
+            (0, "[1,\"x\\ty\"]\nnull\n".to_string())
+        );
+        let pretty = Args {
+            filter: ".a".to_string(),
+            ..Args::default()
+        };
+        assert_eq!(
+            output(pretty, input),
+            (0, "[\n  1,\n  \"x\\ty\"\n]\nnull\n".to_string())
+        );
+        let raw = Args {
+            raw: true,
+            ..compact(".a[1]")
+        };
+        assert_eq!(output(raw, input), (0, "x\ty\nnull\n".to_string()));
+        let slurp = Args {
+            slurp: true,
+            ..compact("map(.a)")
+        };
+        assert_eq!(
+            output(slurp, input),
+            (0, "[[1,\"x\\ty\"],null]\n".to_string())
+        );
+        let null = Args {
+            null_input: true,
+            ..compact(".")
+        };
+        assert_eq!(output(null, input), (0, "null\n".to_string()));
+
+        // missing keys and indices are null, as in jq
+        assert_eq!(
+            output(compact(".a[0], .b.c"), input),
+            (0, "1\nnull\nnull\nnull\n".to_string())
+        );
+        // every output of a stream is written on its own
+        assert_eq!(
+            output(compact(".a[]"), "{\"a\": [1, \"x\\ty\"]}"),
+            (0, "1\n\"x\\ty\"\n".to_string())
+        );
+        assert_eq!(
+            output(compact("{a: .a[0]}, .a"), "{\"a\": [2]}"),
+            (0, "{\"a\":2}\n[2]\n".to_string())
+        );
+
+        // the filter fails on the second value
+        let (code, out) = output(compact(".a[0]"), "{\"a\": [1]}\n{\"a\": 2}\n");
+        assert_eq!((code, out.as_str()), (5, "1\n"));
+        // the rest of a value the filter failed on is skipped
+        let input = "{\"a\": [1]}\n{\"a\": {\"b\": [\"]}\\\"\"]}}\n{\"a\": [4]}";
+        assert_eq!(output(compact(".a[0]"), input), (5, "1\n4\n".to_string()));
+        assert_eq!(
+            output(compact(".a"), "1{\"a\": 2}-3[4]"),
+            (5, "2\n".to_string())
+        );
+        // or the second value does not parse, after part of it was written
+        let (code, out) = output(compact("."), "1 {\"a\": [2, }");
+        assert_eq!((code, out.as_str()), (2, "1\n{\"a\":[2,\n"));
+    }
+
+    #[test]
+    fn exit_status() {
+        let exit = |filter: &str| Args {
+            compact: true,
+            exit_status: true,
+            filter: filter.to_string(),
+            ..Args::default()
+        };
+        let input = "{\"a\": 1, \"b\": false}\n{\"a\": 2}\n";
+        assert_eq!(output(exit(".a"), input).0, 0);
+        assert_eq!(output(exit(".b"), input).0, 1);
+        assert_eq!(output(exit(".c"), input).0, 1);
+        assert_eq!(output(exit(".[] | select(. == 3)"), input).0, 4);
+        assert_eq!(output(exit(".c"), "").0, 4);
+
+        let inputs = [(
+            "missing".to_string(),
+            Err::<io::Empty, _>(io::ErrorKind::NotFound.into()),
+        )];
+        let code = run(
+            &exit("."),
+            jq::parse(".").unwrap(),
+            inputs,
+            Writer::new(io::sink()),
+        );
+        assert_eq!(code, 2);
+    }
+}
@@ -1,15 +1,18 @@
 use std::marker::PhantomData;
 
 use crate::{
-    FilterChain, TakeWrapper,
-    borrow::Unborrow,
-    raw::{RawDeserializeSeed, RawValue2, WithRawValue},
+    FilterChain, Final, TakeWrapper,
+    content::{Content, Replay},
+    predicate::FilterPredicate,
+    raw::RawValue2,
+    track::{self, Key, Segment},
 };
 use serde::de::{
-    self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess,
+    self, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess, SeqAccess,
     value::{MapAccessDeserializer, SeqAccessDeserializer},
 };
 
+#[derive(Clone, Copy, Debug)]
 pub struct Map<F>(pub F);
 
 impl<'de, F> FilterChain<'de> for Map<F>
@@ -49,6 +52,7 @@ where
             .deserialize(SeqAccessDeserializer::new(MapValuesSeqAccess {
                 filter: self.filter,
                 seq,
+                index: 0,
             }))
             .map_err(de::Error::custom)
     }
@@ -64,6 +68,7 @@ where
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct MapValues<F>(pub F);
 
 impl<'de, F> FilterChain<'de> for MapValues<F>
@@ -103,6 +108,7 @@ where
             .deserialize(SeqAccessDeserializer::new(MapValuesSeqAccess {
                 filter: self.filter,
                 seq,
+                index: 0,
             }))
             .map_err(de::Error::custom)
     }
@@ -111,6 +117,7 @@ where
     fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
         self.seed
             .deserialize(MapAccessDeserializer::new(MapValuesMapAccess {
+                key: AnyKey,
                 filter: self.filter,
                 value: None,
                 map,
@@ -119,34 +126,97 @@ where
     }
 }
 
+/// Like [`MapValues`], but only the entries of a map whose key the predicate holds for.
+/// The key is a string.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyFilter<P, F>(pub P, pub F);
+
+impl<'de, P, F> FilterChain<'de> for KeyFilter<P, F>
+where
+    P: FilterPredicate<'de> + Clone,
+    F: FilterChain<'de> + Clone,
+{
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        deserializer.deserialize_map(KeyFilterVisitor {
+            key: self.0,
+            filter: self.1,
+            seed,
+        })
+    }
+}
+
+struct KeyFilterVisitor<P, F, S> {
+    key: P,
+    filter: F,
+    seed: S,
+}
+
+impl<'de, P, F, S> de::Visitor<'de> for KeyFilterVisitor<P, F, S>
+where
+    S: de::DeserializeSeed<'de>,
+    P: FilterPredicate<'de> + Clone,
+    F: FilterChain<'de> + Clone,
+{
+    type Value = S::Value;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map")
+    }
+
+    #[inline]
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.seed
+            .deserialize(MapAccessDeserializer::new(MapValuesMapAccess {
+                key: self.key,
+                filter: self.filter,
+                value: None,
+                map,
+            }))
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The key predicate of [`MapValues`], which keeps every entry.
+#[derive(Clone, Copy)]
+struct AnyKey;
+
+impl<'de> FilterPredicate<'de> for AnyKey {
+    fn filter<D>(self, _: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        Ok(true)
+    }
+}
+
 struct Filter<'a, F, S> {
     seed: &'a mut TakeWrapper<S>,
     filter: F,
 }
 
-impl<'de, F, S> RawDeserializeSeed<'de> for Filter<'_, F, S>
+impl<'de, F, S> DeserializeSeed<'de> for Filter<'_, F, S>
 where
     F: FilterChain<'de>,
     S: DeserializeSeed<'de>,
 {
+    /// `None` if the filter did not match, leaving the seed for the next value.
     type Value = Option<S::Value>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
-        D: de::Deserializer<'de> + Clone,
+        D: de::Deserializer<'de>,
     {
-        let res = self.filter.filter(&mut *self.seed, deserializer.clone());
-        match res {
-            Ok(val) => Ok(Some(val)),
-            Err(_) if self.seed.0.is_some() => Ok(None),
-            Err(err) => Err(de::Error::custom(err)),
-        }
+        self.filter.filter_opt(&mut *self.seed, deserializer)
     }
 }
 
 struct MapValuesSeqAccess<F, S> {
     filter: F,
     seq: S,
+    index: usize,
 }
 
 impl<'de, F, S> SeqAccess<'de> for MapValuesSeqAccess<F, S>
@@ -168,7 +238,9 @@ where
                 seed: &mut seed,
             };
 
-            match self.seq.next_element_seed(WithRawValue(filter))? {
+            let index = self.index;
+            self.index += 1;
+            match track::at(self.seq.next_element_seed(filter), || Segment::Index(index))? {
                 Some(Some(val)) => break Ok(Some(val)),
                 Some(None) => continue,
                 None => break Ok(None),
@@ -195,33 +267,48 @@ where
     {
         let mut seed = TakeWrapper(Some(seed));
 
-        loop {
+        while let Some(key) = Key::next(&mut self.map)? {
             let filter = Filter {
                 filter: self.filter.clone(),
                 seed: &mut seed,
             };
 
-            match self
-                .map
-                .next_entry_seed(PhantomData::<IgnoredAny>, WithRawValue(filter))?
-            {
-                Some((_key, Some(val))) => break Ok(Some(val)),
-                Some((_key, None)) => continue,
-                None => break Ok(None),
+            if let Some(val) = track::at(self.map.next_value_seed(filter), || key.segment())? {
+                return Ok(Some(val));
             }
         }
+        Ok(None)
     }
 }
 
-struct MapValuesMapAccess<'de, F, M> {
+struct MapValuesMapAccess<'de, P, F, M> {
+    key: P,
     filter: F,
     value: Option<RawValue2<'de>>,
     map: M,
 }
 
-impl<'de, F, S> MapAccess<'de> for MapValuesMapAccess<'de, F, S>
+impl<'de, P, F, M> MapValuesMapAccess<'de, P, F, M>
+where
+    P: FilterPredicate<'de> + Clone,
+    M: MapAccess<'de>,
+{
+    /// The next key the predicate holds for, skipping the values of the others.
+    fn next_key(&mut self) -> Result<Option<RawValue2<'de>>, M::Error> {
+        while let Some(key) = self.map.next_key::<RawValue2<'de>>()? {
+            if key.test(self.key.clone()).map_err(de::Error::custom)? {
+                return Ok(Some(key));
+            }
+            self.map.next_value::<IgnoredAny>()?;
+        }
+        Ok(None)
+    }
+}
+
+impl<'de, P, F, S> MapAccess<'de> for MapValuesMapAccess<'de, P, F, S>
 where
     S: MapAccess<'de>,
+    P: FilterPredicate<'de> + Clone,
     F: FilterChain<'de> + Clone,
 {
     type Error = S::Error;
@@ -237,21 +324,17 @@ where
     {
         let mut vseed = TakeWrapper(Some(vseed));
         loop {
-            let vfilter = WithRawValue(Filter {
+            let vfilter = Filter {
                 filter: self.filter.clone(),
                 seed: &mut vseed,
-            });
-            let kfilter = PhantomData::<RawValue2<'de>>;
-
-            match self.map.next_entry_seed(kfilter, vfilter)? {
-                Some((key, Some(val))) => {
-                    let key = kseed
-                        .deserialize(Unborrow(&*key, PhantomData))
-                        .map_err(de::Error::custom)?;
-                    break Ok(Some((key, val)));
-                }
-                Some((_key, None)) => continue,
-                None => break Ok(None),
+            };
+            let Some(key) = self.next_key()? else {
+                break Ok(None);
+            };
+            let val = self.map.next_value_seed(vfilter);
+            if let Some(val) = track::at(val, || Segment::raw_key(&key))? {
+                let key = key.deserialize_into(kseed).map_err(de::Error::custom)?;
+                break Ok(Some((key, val)));
             }
         }
     }
@@ -262,23 +345,19 @@ where
     {
         let mut vseed = TakeWrapper(Some(PhantomData::<RawValue2<'de>>));
         loop {
-            let vfilter = WithRawValue(Filter {
+            let vfilter = Filter {
                 filter: self.filter.clone(),
                 seed: &mut vseed,
-            });
-            let kfilter = PhantomData::<RawValue2<'de>>;
-
-            match self.map.next_entry_seed(kfilter, vfilter)? {
-                Some((key, Some(val))) => {
-                    let key = seed
-                        .deserialize(Unborrow(&*key, PhantomData))
-                        .map_err(de::Error::custom)?;
-
-                    self.value = Some(val);
-                    break Ok(Some(key));
-                }
-                Some((_key, None)) => continue,
-                None => break Ok(None),
+            };
+            let Some(key) = self.next_key()? else {
+                break Ok(None);
+            };
+            let val = self.map.next_value_seed(vfilter);
+            if let Some(val) = track::at(val, || Segment::raw_key(&key))? {
+                let key = key.deserialize_into(seed).map_err(de::Error::custom)?;
+
+                self.value = Some(val);
+                break Ok(Some(key));
             }
         }
     }
@@ -287,11 +366,490 @@ where
     where
         V: DeserializeSeed<'de>,
     {
-        match self.value.as_ref().unwrap() {
-            RawValue2::Borrowed(val) => seed.deserialize(*val).map_err(de::Error::custom),
-            RawValue2::Owned(val) => seed
-                .deserialize(Unborrow(&**val, PhantomData))
-                .map_err(de::Error::custom),
+        self.value
+            .as_ref()
+            .unwrap()
+            .deserialize_into(seed)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Passes every value of a sequence or a map to the seed as a sequence, like `.[]` in jq.
+///
+/// In an [`hlist!`](crate::hlist), the rest of the chain runs on that sequence; use
+/// [`Each::then`] to run it on every value instead.
+#[derive(Clone, Copy, Debug)]
+pub struct Each;
+
+impl Each {
+    /// Applies `rest` to every value, like `.[] | rest` in jq.
+    pub fn then<F>(self, rest: F) -> Map<F>
+    where
+        F: Clone,
+    {
+        Map(rest)
+    }
+}
+
+impl<'de> FilterChain<'de> for Each {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        Map(Final).filter(seed, deserializer)
+    }
+}
+
+/// Like [`Each`], but passes `[key, value]` pairs to the seed.
+///
+/// Sequences use the element index as the key.
+#[derive(Clone, Copy, Debug)]
+pub struct EachEntry;
+
+impl<'de> FilterChain<'de> for EachEntry {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        deserializer.deserialize_any(EachEntryVisitor { seed })
+    }
+}
+
+struct EachEntryVisitor<S> {
+    seed: S,
+}
+
+impl<'de, S> de::Visitor<'de> for EachEntryVisitor<S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a sequence or a map")
+    }
+
+    #[inline]
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.seed
+            .deserialize(SeqAccessDeserializer::new(EntrySeqAccess { seq, index: 0 }))
+    }
+
+    #[inline]
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.seed
+            .deserialize(SeqAccessDeserializer::new(EntryMapSeqAccess { map }))
+    }
+}
+
+struct EntrySeqAccess<A> {
+    seq: A,
+    index: usize,
+}
+
+impl<'de, A> SeqAccess<'de> for EntrySeqAccess<A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let index = self.index;
+        self.index += 1;
+        self.seq.next_element_seed(IndexEntrySeed { index, seed })
+    }
+}
+
+/// Pairs the element with its index once the sequence has produced one.
+struct IndexEntrySeed<S> {
+    index: usize,
+    seed: S,
+}
+
+impl<'de, S> DeserializeSeed<'de> for IndexEntrySeed<S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut entry = IndexEntryAccess {
+            index: Some(self.index),
+            value: Some(deserializer),
+        };
+        let v = self
+            .seed
+            .deserialize(SeqAccessDeserializer::new(&mut entry))?;
+        if let Some(value) = entry.value {
+            value.deserialize_ignored_any(IgnoredAny)?;
+        }
+        Ok(v)
+    }
+}
+
+/// The index, then the element.
+struct IndexEntryAccess<D> {
+    index: Option<usize>,
+    value: Option<D>,
+}
+
+impl<'de, D> SeqAccess<'de> for IndexEntryAccess<D>
+where
+    D: de::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some(index) = self.index.take() {
+            return seed.deserialize(index.into_deserializer()).map(Some);
+        }
+        match self.value.take() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct EntryMapSeqAccess<M> {
+    map: M,
+}
+
+impl<'de, M> SeqAccess<'de> for EntryMapSeqAccess<M>
+where
+    M: MapAccess<'de>,
+{
+    type Error = M::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let Some(key) = self.map.next_key::<RawValue2<'de>>()? else {
+            return Ok(None);
+        };
+        let mut entry = MapEntryAccess {
+            key: Some(key),
+            map: &mut self.map,
+            value: true,
+        };
+        let v = seed.deserialize(SeqAccessDeserializer::new(&mut entry))?;
+        if entry.value {
+            self.map.next_value::<IgnoredAny>()?;
         }
+        Ok(Some(v))
+    }
+}
+
+/// The key, then the value of the current map entry.
+struct MapEntryAccess<'a, 'de, M> {
+    key: Option<RawValue2<'de>>,
+    map: &'a mut M,
+    value: bool,
+}
+
+impl<'de, M> SeqAccess<'de> for MapEntryAccess<'_, 'de, M>
+where
+    M: MapAccess<'de>,
+{
+    type Error = M::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some(key) = self.key.take() {
+            return key
+                .deserialize_into(seed)
+                .map(Some)
+                .map_err(de::Error::custom);
+        }
+        if std::mem::take(&mut self.value) {
+            return self.map.next_value_seed(seed).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+/// Applies the filter to a value and every value nested in it, in document order,
+/// passing each match to the seed as a sequence.
+///
+/// Values the filter does not match are skipped, like in [`Map`]. A value is matched
+/// before the values nested in it, so it is read once into a [`Content`] that the filter
+/// replays, and the values nested in it are visited there by reference.
+#[derive(Clone, Copy, Debug)]
+pub struct Recurse<F> {
+    filter: F,
+    max_depth: usize,
+}
+
+impl<F> Recurse<F> {
+    pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+    pub fn new(filter: F) -> Self {
+        Self {
+            filter,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Fails on values nested deeper than `max_depth`, the outer value being at depth 0.
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+}
+
+impl<'de, F> FilterChain<'de> for Recurse<F>
+where
+    F: FilterChain<'de> + Clone,
+{
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        let human_readable = deserializer.is_human_readable();
+        let content = <Content as de::Deserialize>::deserialize(deserializer)?;
+        seed.deserialize(SeqAccessDeserializer::new(RecurseSeqAccess {
+            filter: self.filter,
+            max_depth: self.max_depth,
+            root: Some(Replay::new(&content, human_readable)),
+            stack: Vec::new(),
+        }))
+        .map_err(de::Error::custom)
+    }
+}
+
+struct RecurseSeqAccess<'a, 'de, F> {
+    filter: F,
+    max_depth: usize,
+    /// the value being recursed into, until it is visited.
+    root: Option<Replay<'a, 'de>>,
+    /// the values left to visit at each depth below the root.
+    stack: Vec<Level<'a, 'de>>,
+}
+
+/// The values directly inside a sequence or a map.
+struct Level<'a, 'de> {
+    parent: Replay<'a, 'de>,
+    children: Children<'a, 'de>,
+    /// the key of the last visited value, `None` in a sequence.
+    key: Option<&'a Content<'de>>,
+    next: usize,
+}
+
+enum Children<'a, 'de> {
+    Seq(std::slice::Iter<'a, Content<'de>>),
+    Map(std::slice::Iter<'a, (Content<'de>, Content<'de>)>),
+}
+
+impl<'a, 'de> Level<'a, 'de> {
+    /// The level of the values nested in `parent`, if any.
+    fn new(parent: Replay<'a, 'de>) -> Option<Self> {
+        let children = match parent.content() {
+            Content::Seq(elements) if !elements.is_empty() => Children::Seq(elements.iter()),
+            Content::Map(entries) if !entries.is_empty() => Children::Map(entries.iter()),
+            Content::Some(v) => return Self::new(parent.nested(v)),
+            _ => return None,
+        };
+        Some(Self {
+            parent,
+            children,
+            key: None,
+            next: 0,
+        })
+    }
+
+    fn next(&mut self) -> Option<Replay<'a, 'de>> {
+        let value = match &mut self.children {
+            Children::Seq(elements) => elements.next()?,
+            Children::Map(entries) => {
+                let (key, value) = entries.next()?;
+                self.key = Some(key);
+                value
+            }
+        };
+        self.next += 1;
+        Some(self.parent.nested(value))
+    }
+
+    /// The segment of the last visited value.
+    fn segment(&self) -> Segment {
+        match self.key {
+            Some(key) => Segment::content_key(key),
+            None => Segment::Index(self.next - 1),
+        }
+    }
+}
+
+impl<'a, 'de, F> RecurseSeqAccess<'a, 'de, F>
+where
+    F: FilterChain<'de> + Clone,
+{
+    /// Runs the filter on the value, then descends into it.
+    fn visit<T>(
+        &mut self,
+        node: Replay<'a, 'de>,
+        seed: &mut TakeWrapper<T>,
+    ) -> Result<Option<T::Value>, serde_json::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let filter = Filter {
+            filter: self.filter.clone(),
+            seed,
+        };
+        let val = filter.deserialize(node)?;
+
+        if let Some(level) = Level::new(node) {
+            if self.stack.len() >= self.max_depth {
+                return Err(de::Error::custom(format_args!(
+                    "recursion limit of {} exceeded",
+                    self.max_depth
+                )));
+            }
+            self.stack.push(level);
+        }
+        Ok(val)
+    }
+}
+
+impl<'de, F> SeqAccess<'de> for RecurseSeqAccess<'_, 'de, F>
+where
+    F: FilterChain<'de> + Clone,
+{
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let mut seed = TakeWrapper(Some(seed));
+
+        if let Some(root) = self.root.take()
+            && let Some(val) = self.visit(root, &mut seed)?
+        {
+            return Ok(Some(val));
+        }
+        loop {
+            let Some(level) = self.stack.last_mut() else {
+                break Ok(None);
+            };
+            let Some(node) = level.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            let depth = self.stack.len();
+            let val = self.visit(node, &mut seed);
+            let val = self.stack[..depth]
+                .iter()
+                .rev()
+                .fold(val, |val, level| track::at(val, || level.segment()));
+            if let Some(val) = val? {
+                break Ok(Some(val));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::json;
+
+    use super::{Each, EachEntry, Recurse};
+    use crate::{FilterChain, hlist, optional::Optional};
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn each() {
+        let json = json!({ "a": {"x": 1, "y": 2}, "b": [3, 4] }).to_string();
+
+        let v: Vec<u32> = extract_json_path(&json, hlist!["a", Each]).unwrap();
+        assert_eq!(v, [1, 2]);
+        let v: Vec<u32> = extract_json_path(&json, hlist!["b", Each]).unwrap();
+        assert_eq!(v, [3, 4]);
+
+        // `then` applies the rest of the chain to each value, like `.[]` in jq
+        let json = json!({"b": {"c": [2, 3, 4], "d": [5]}}).to_string();
+        let v: Vec<u32> = extract_json_path(&json, hlist!["b", Each.then(hlist![0])]).unwrap();
+        assert_eq!(v, [2, 5]);
+        let v: Vec<u32> = extract_json_path(&json, hlist!["b", Each, 0]).unwrap();
+        assert_eq!(v, [2, 3, 4]);
+        let v: Vec<u32> = extract_json_path(&json, crate::path!(.b[][0])).unwrap();
+        assert_eq!(v, [2, 5]);
+        let v: Vec<u32> = extract_json_path(&json, crate::jq::parse("[.b[][0]]").unwrap()).unwrap();
+        assert_eq!(v, [2, 5]);
+
+        let json = json!({ "a": {"x": 1, "y": 2}, "b": [3, 4] }).to_string();
+
+        let v: Vec<(String, u32)> = extract_json_path(&json, hlist!["a", EachEntry]).unwrap();
+        assert_eq!(v, [("x".to_string(), 1), ("y".to_string(), 2)]);
+        let v: Vec<(usize, u32)> = extract_json_path(&json, hlist!["b", EachEntry]).unwrap();
+        assert_eq!(v, [(0, 3), (1, 4)]);
+
+        // entries that are not fully read are skipped.
+        let v: Vec<(&str,)> = extract_json_path(&json, hlist!["a", EachEntry]).unwrap();
+        assert_eq!(v, [("x",), ("y",)]);
+        let v: Vec<(usize,)> = extract_json_path(&json, hlist!["b", EachEntry]).unwrap();
+        assert_eq!(v, [(0,), (1,)]);
+    }
+
+    #[test]
+    fn recurse() {
+        let json = json!({
+            "store": {
+                "book": [{"price": 8}, {"price": 12, "isbn": "x"}],
+                "bicycle": {"price": 20}
+            }
+        })
+        .to_string();
+
+        let prices: Vec<u32> =
+            extract_json_path(&json, hlist![Recurse::new(Optional("price"))]).unwrap();
+        assert_eq!(prices, [20, 8, 12]);
+
+        let all: Vec<serde_json::Value> =
+            extract_json_path(&json, hlist!["store", "book", Recurse::new(hlist![])]).unwrap();
+        let all: Vec<_> = all.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            all,
+            [
+                r#"[{"price":8},{"isbn":"x","price":12}]"#,
+                r#"{"price":8}"#,
+                "8",
+                r#"{"isbn":"x","price":12}"#,
+                r#""x""#,
+                "12",
+            ]
+        );
+
+        let filter = hlist![Recurse::new(Optional("price")).with_max_depth(3)];
+        let err = extract_json_path::<_, Vec<u32>>(&json, filter).unwrap_err();
+        assert!(err.to_string().contains("recursion limit of 3"), "{err}");
+        let filter = hlist![Recurse::new(Optional("price")).with_max_depth(4)];
+        let prices: Vec<u32> = extract_json_path(&json, filter).unwrap();
+        assert_eq!(prices, [20, 8, 12]);
     }
 }
@@ -1,30 +1,58 @@
-use crate::{FilterChain, predicate::FilterPredicate};
+use crate::{FilterChain, predicate::FilterPredicate, raw::RawValue2};
 use serde::de::{
     self, DeserializeSeed, MapAccess, SeqAccess,
-    value::{MapAccessDeserializer, SeqAccessDeserializer},
+    value::{BorrowedStrDeserializer, MapAccessDeserializer, SeqAccessDeserializer},
 };
-use serde_json::value::RawValue;
 
+/// Keeps the elements of a sequence, or the entries of a map, whose value the predicate holds for.
+#[derive(Clone, Copy, Debug)]
 pub struct MapSelect<F>(pub F);
 
-impl<'de, F> FilterChain<'de> for MapSelect<F>
-where
-    F: FilterPredicate<'de> + Clone,
-{
-    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
-    where
-        D: serde::de::Deserializer<'de>,
-        S: de::DeserializeSeed<'de>,
-    {
-        deserializer.deserialize_any(MapSelectVisitor {
-            filter: self.0,
-            seed,
-        })
-    }
+/// Keeps the entries of a map whose key the predicate holds for. The key is a string.
+#[derive(Clone, Copy, Debug)]
+pub struct MapSelectKeys<F>(pub F);
+
+/// Keeps the entries of a map that the predicate holds for, given as `{"key": k, "value": v}`
+/// like jq's `with_entries`.
+#[derive(Clone, Copy, Debug)]
+pub struct MapSelectEntries<F>(pub F);
+
+/// What the predicate of a map entry is run on.
+#[derive(Clone, Copy, Debug)]
+enum On {
+    Value,
+    Key,
+    Entry,
 }
 
+macro_rules! map_select {
+    ($name:ident, $on:expr) => {
+        impl<'de, F> FilterChain<'de> for $name<F>
+        where
+            F: FilterPredicate<'de> + Clone,
+        {
+            fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+                S: de::DeserializeSeed<'de>,
+            {
+                deserializer.deserialize_any(MapSelectVisitor {
+                    filter: self.0,
+                    on: $on,
+                    seed,
+                })
+            }
+        }
+    };
+}
+
+map_select!(MapSelect, On::Value);
+map_select!(MapSelectKeys, On::Key);
+map_select!(MapSelectEntries, On::Entry);
+
 struct MapSelectVisitor<F, S> {
     filter: F,
+    on: On,
     seed: S,
 }
 
@@ -35,11 +63,17 @@ where
 {
     type Value = S::Value;
     fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "a sequence or a map")
+        match self.on {
+            On::Value => write!(f, "a sequence or a map"),
+            On::Key | On::Entry => write!(f, "a map"),
+        }
     }
 
     #[inline]
     fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        if !matches!(self.on, On::Value) {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &self));
+        }
         self.seed
             .deserialize(SeqAccessDeserializer::new(MapSelectSeqAccess {
                 filter: self.filter,
@@ -53,6 +87,7 @@ where
         self.seed
             .deserialize(MapAccessDeserializer::new(MapSelectMapAccess {
                 filter: self.filter,
+                on: self.on,
                 value: None,
                 map,
             }))
@@ -76,9 +111,9 @@ where
     where
         T: DeserializeSeed<'de>,
     {
-        while let Some(val) = self.seq.next_element::<&'de RawValue>()? {
-            if self.filter.clone().filter(val).map_err(de::Error::custom)? {
-                return Ok(Some(seed.deserialize(val).map_err(de::Error::custom)?));
+        while let Some(val) = self.seq.next_element::<RawValue2<'de>>()? {
+            if val.test(self.filter.clone()).map_err(de::Error::custom)? {
+                return Ok(Some(val.deserialize_into(seed).map_err(de::Error::custom)?));
             }
         }
         Ok(None)
@@ -87,10 +122,83 @@ where
 
 struct MapSelectMapAccess<'de, F, M> {
     filter: F,
-    value: Option<&'de RawValue>,
+    on: On,
+    value: Option<RawValue2<'de>>,
     map: M,
 }
 
+impl<'de, F, M> MapSelectMapAccess<'de, F, M>
+where
+    F: FilterPredicate<'de> + Clone,
+{
+    fn test(&self, key: &RawValue2<'de>, val: &RawValue2<'de>) -> Result<bool, serde_json::Error> {
+        let filter = self.filter.clone();
+        match self.on {
+            On::Value => val.test(filter),
+            On::Key => key.test(filter),
+            On::Entry => filter.filter(Entry { key, val }),
+        }
+    }
+}
+
+/// A map entry as `{"key": k, "value": v}`, for [`MapSelectEntries`].
+struct Entry<'a, 'de> {
+    key: &'a RawValue2<'de>,
+    val: &'a RawValue2<'de>,
+}
+
+impl<'de> de::Deserializer<'de> for Entry<'_, 'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(EntryAccess {
+            fields: [("key", self.key), ("value", self.val)].into_iter(),
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct EntryAccess<'a, 'de> {
+    fields: std::array::IntoIter<(&'static str, &'a RawValue2<'de>), 2>,
+    value: Option<&'a RawValue2<'de>>,
+}
+
+impl<'de> MapAccess<'de> for EntryAccess<'_, 'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some((name, value)) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::new(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.value.take().unwrap().deserialize_into(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
 impl<'de, F, S> MapAccess<'de> for MapSelectMapAccess<'de, F, S>
 where
     S: MapAccess<'de>,
@@ -107,10 +215,10 @@ where
         K: DeserializeSeed<'de>,
         V: DeserializeSeed<'de>,
     {
-        while let Some((key, val)) = self.map.next_entry::<&'de RawValue, &'de RawValue>()? {
-            if self.filter.clone().filter(val).map_err(de::Error::custom)? {
-                let key = kseed.deserialize(key).map_err(de::Error::custom)?;
-                let val = vseed.deserialize(val).map_err(de::Error::custom)?;
+        while let Some((key, val)) = self.map.next_entry::<RawValue2<'de>, RawValue2<'de>>()? {
+            if self.test(&key, &val).map_err(de::Error::custom)? {
+                let key = key.deserialize_into(kseed).map_err(de::Error::custom)?;
+                let val = val.deserialize_into(vseed).map_err(de::Error::custom)?;
                 return Ok(Some((key, val)));
             }
         }
@@ -121,9 +229,10 @@ where
     where
         K: DeserializeSeed<'de>,
     {
-        while let Some((key, val)) = self.map.next_entry::<&'de RawValue, &'de RawValue>()? {
-            if self.filter.clone().filter(val).map_err(de::Error::custom)? {
-                let key = seed.deserialize(key).map_err(de::Error::custom)?;
+        while let Some((key, val)) = self.map.next_entry::<RawValue2<'de>, RawValue2<'de>>()? {
+            if self.test(&key, &val).map_err(de::Error::custom)? {
+                let key = key.deserialize_into(seed).map_err(de::Error::custom)?;
+                self.value = Some(val);
                 return Ok(Some(key));
             }
         }
@@ -134,7 +243,127 @@ where
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(self.value.unwrap())
-            .map_err(de::Error::custom)
+        let value = self.value.take().unwrap();
+        value.deserialize_into(seed).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, marker::PhantomData};
+
+    use serde_json::json;
+
+    use crate::{FilterChain, hlist};
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn keys() {
+        use super::{MapSelectEntries, MapSelectKeys};
+        use crate::{
+            Final,
+            map::KeyFilter,
+            predicate::{And, EndsWith, Gt, Number, StartsWith},
+        };
+
+        let json = json!({"x-a": 1, "y": 2, "x-b": 3}).to_string();
+        let headers: HashMap<&str, u32> =
+            extract_json_path(&json, MapSelectKeys(StartsWith::new(Final, "x-"))).unwrap();
+        assert_eq!(headers, HashMap::from([("x-a", 1), ("x-b", 3)]));
+
+        let filter = MapSelectEntries(And(
+            StartsWith::new("key", "x-"),
+            Gt::new("value", Number::from(1)),
+        ));
+        let headers: HashMap<&str, u32> = extract_json_path(&json, filter).unwrap();
+        assert_eq!(headers, HashMap::from([("x-b", 3)]));
+
+        let err = extract_json_path::<_, HashMap<&str, u32>>(
+            "[1]",
+            MapSelectKeys(StartsWith::new(Final, "x-")),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected a map"), "{err}");
+
+        let json = json!({"a_ms": {"v": 1}, "b": {"v": 2}, "c_ms": {"v": 3}}).to_string();
+        let filter = KeyFilter(EndsWith::new(Final, "_ms"), "v");
+        let timings: HashMap<&str, u32> = extract_json_path(&json, filter).unwrap();
+        assert_eq!(timings, HashMap::from([("a_ms", 1), ("c_ms", 3)]));
+
+        // keys are owned when read from a reader
+        let timings: HashMap<String, u32> = filter
+            .filter(
+                PhantomData::<HashMap<String, u32>>,
+                &mut serde_json::Deserializer::from_reader(json.as_bytes()),
+            )
+            .unwrap();
+        assert_eq!(timings.len(), 2);
+    }
+
+    #[test]
+    fn map_select_reader() {
+        use super::{MapSelect, MapSelectEntries, MapSelectKeys};
+        use crate::{
+            Final,
+            predicate::{Has, NotEq, StartsWith},
+        };
+
+        fn from_reader<'de, F, T>(json: &str, filter: F) -> T
+        where
+            F: FilterChain<'de>,
+            T: serde::de::DeserializeOwned,
+        {
+            let mut de = serde_json::Deserializer::from_reader(json.as_bytes());
+            let value = filter.filter(PhantomData::<T>, &mut de).unwrap();
+            de.end().unwrap();
+            value
+        }
+
+        let json = json!({ "traceEvents": [
+            {"name": "a", "ph": "X", "args": {}},
+            {"name": "b", "ph": "B"},
+            {"name": "c", "ph": "E", "args": {"x": 1}},
+        ]})
+        .to_string();
+        let events: Vec<serde_json::Value> =
+            from_reader(&json, hlist!["traceEvents", MapSelect(Has("args"))]);
+        assert_eq!(events.len(), 2);
+        let events: Vec<serde_json::Value> = from_reader(
+            &json,
+            hlist!["traceEvents", MapSelect(NotEq::new("ph", "X".to_string()))],
+        );
+        assert_eq!(
+            events,
+            [
+                json!({"name": "b", "ph": "B"}),
+                json!({"name": "c", "ph": "E", "args": {"x": 1}})
+            ]
+        );
+
+        let json = json!({"x-a": {"n": 1}, "y": {"n": 2}}).to_string();
+        let headers: HashMap<String, serde_json::Value> =
+            from_reader(&json, MapSelectKeys(StartsWith::new(Final, "x-")));
+        assert_eq!(
+            headers,
+            HashMap::from([("x-a".to_string(), json!({"n": 1}))])
+        );
+        let headers: HashMap<String, serde_json::Value> = from_reader(
+            &json,
+            MapSelect(NotEq::new("n", crate::predicate::Number::from(1))),
+        );
+        assert_eq!(headers, HashMap::from([("y".to_string(), json!({"n": 2}))]));
+        let headers: HashMap<String, serde_json::Value> =
+            from_reader(&json, MapSelectEntries(StartsWith::new("key", "y")));
+        assert_eq!(headers.len(), 1);
     }
 }
@@ -0,0 +1,244 @@
+//! Filtering MessagePack with rmp-serde, and writing the output of a filter back out as
+//! MessagePack.
+//!
+//! rmp-serde deserializers work with the filters as they are. Entries a filter does not select
+//! are skipped, and values it has to read twice are buffered as
+//! [`Content`](crate::content::Content). Use [`Deserializer::from_read_ref`] to borrow strings
+//! and bytes from the input.
+//!
+//! ```
+//! use serde_path::{FilterChain, msgpack::MsgpackSer};
+//!
+//! let input = rmp_serde::to_vec(&serde_json::json!({"a": 1, "b": {"c": [2, 3]}})).unwrap();
+//! let mut de = rmp_serde::Deserializer::from_read_ref(&input);
+//! let ser = rmp_serde::Serializer::new(Vec::new());
+//! let output = serde_path::path!(.b.c).filter(MsgpackSer(ser), &mut de).unwrap();
+//! assert_eq!(output, rmp_serde::to_vec(&[2, 3]).unwrap());
+//! ```
+
+use std::io;
+
+use rmp_serde::config::SerializerConfig;
+use serde::de::{DeserializeSeed, Deserializer};
+
+use crate::json_ser::SerWrapper;
+
+/// Writes the value it is given with the serializer, returning its writer: the MessagePack
+/// counterpart of [`JsonSer`](crate::json_ser::JsonSer).
+pub struct MsgpackSer<W, C = rmp_serde::config::DefaultConfig>(pub rmp_serde::Serializer<W, C>);
+
+impl<'de, W, C> DeserializeSeed<'de> for MsgpackSer<W, C>
+where
+    W: io::Write,
+    C: SerializerConfig,
+{
+    type Value = W;
+
+    fn deserialize<D: Deserializer<'de>>(mut self, d: D) -> Result<W, D::Error> {
+        SerWrapper(&mut self.0).deserialize(d)?;
+        Ok(self.0.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, marker::PhantomData};
+
+    use serde_json::{Value, json};
+
+    use super::MsgpackSer;
+    use crate::{
+        FilterChain, MultiMap, MultiVec, hlist, json_path,
+        map::Map,
+        predicate::{Has, NotEq},
+        select::Select,
+    };
+
+    fn extract_msgpack_path<'de, F, T>(
+        msgpack: &'de [u8],
+        filter: F,
+    ) -> Result<T, rmp_serde::decode::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut rmp_serde::Deserializer::from_read_ref(msgpack),
+        )
+    }
+
+    #[test]
+    fn list() {
+        let msgpack = rmp_serde::to_vec(&json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [5]
+            },
+            "e": 6,
+        }))
+        .unwrap();
+
+        let path = json_path!(@["b"][@["c"][1], @["d"][0]]);
+        let fields: Value = extract_msgpack_path(&msgpack, path).unwrap();
+        assert_eq!(fields, json!([3, 5]));
+
+        let path = json_path!(@["b"][@["c"][-1], @["d"][-1]]);
+        let fields: Value = extract_msgpack_path(&msgpack, path).unwrap();
+        assert_eq!(fields, json!([4, 5]));
+
+        let path = json_path!(@["b"]["c"]..);
+        let fields: Value = extract_msgpack_path(&msgpack, path).unwrap();
+        assert_eq!(fields, json!([[2, 3, 4], 2, 3, 4]));
+
+        let fields: Vec<u32> = extract_msgpack_path(
+            &msgpack,
+            hlist!["b", MultiVec(vec![hlist!["c", 1_usize], hlist!["d", 0]])],
+        )
+        .unwrap();
+        assert_eq!(fields, [3, 5]);
+
+        // the reader does not borrow, so neither do the filters
+        let fields: Vec<u32> = crate::path!(.b.c[1:])
+            .filter(PhantomData, &mut rmp_serde::Deserializer::new(&msgpack[..]))
+            .unwrap();
+        assert_eq!(fields, [3, 4]);
+    }
+
+    #[test]
+    fn map() {
+        let msgpack = rmp_serde::to_vec(&json!({
+            "a": 1,
+            "b": {
+                "c": [2, 3, 4],
+                "d": [{"e": 5}]
+            },
+        }))
+        .unwrap();
+
+        let path = json_path!(@["b"]{
+            "one": @["c"][1],
+            "two": @["d"][0][@["e"], @],
+        });
+        let fields: Value = extract_msgpack_path(&msgpack, path).unwrap();
+        assert_eq!(fields, json!({"one": 3, "two": [5, {"e": 5}]}));
+
+        let fields: HashMap<String, u32> = extract_msgpack_path(
+            &msgpack,
+            hlist![
+                "b",
+                MultiMap(vec![
+                    ("one".to_string(), hlist!["c", 1_usize]),
+                    ("two".to_string(), hlist!["c", 2])
+                ])
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            fields,
+            HashMap::from([("one".to_string(), 3), ("two".to_string(), 4)])
+        );
+    }
+
+    #[test]
+    fn select() {
+        let msgpack = rmp_serde::to_vec(&json!({ "events": [
+            {"name": "a", "ph": "X"},
+            {"name": "b", "ph": "B", "args": null},
+            {"name": "c", "ph": "E"},
+        ]}))
+        .unwrap();
+
+        let names: Vec<&str> = extract_msgpack_path(
+            &msgpack,
+            hlist![
+                "events",
+                Map(hlist![Select(NotEq::new("ph", "X".to_string())), "name"])
+            ],
+        )
+        .unwrap();
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn compact() {
+        use std::net::IpAddr;
+
+        // msgpack is not human readable, so an `IpAddr` is written as an enum of octets
+        let hosts = HashMap::from([(
+            "hosts",
+            [HashMap::from([("ip", IpAddr::from([10, 0, 0, 1]))])],
+        )]);
+        let msgpack = rmp_serde::to_vec(&hosts).unwrap();
+
+        let ips: Vec<IpAddr> = extract_msgpack_path(&msgpack, hlist!["hosts", Map("ip")]).unwrap();
+        assert_eq!(ips, [IpAddr::from([10, 0, 0, 1])]);
+
+        // and buffered values are replayed that way
+        let ips: Vec<IpAddr> = extract_msgpack_path(
+            &msgpack,
+            hlist!["hosts", Map(hlist![Select(Has("ip")), "ip"])],
+        )
+        .unwrap();
+        assert_eq!(ips, [IpAddr::from([10, 0, 0, 1])]);
+    }
+
+    #[test]
+    fn output() {
+        let msgpack = rmp_serde::to_vec(&json!({
+            "a": 1,
+            "b": {
+                "c": [2, -3, "x"],
+                "d": [{"e": 5.5, "f": null, "g": true}]
+            },
+        }))
+        .unwrap();
+
+        let path = json_path!(@["b"][@["c"], @["d"][0]]);
+        let ser = rmp_serde::Serializer::new(Vec::new());
+        let output = path
+            .filter(
+                MsgpackSer(ser),
+                &mut rmp_serde::Deserializer::from_read_ref(&msgpack),
+            )
+            .unwrap();
+        let output: Value = rmp_serde::from_slice(&output).unwrap();
+        assert_eq!(
+            output,
+            json!([[2, -3, "x"], {"e": 5.5, "f": null, "g": true}])
+        );
+
+        // bytes stay bytes
+        let msgpack = rmp_serde::to_vec(&HashMap::from([("a", bytes(b"\x00\xff"))])).unwrap();
+        let ser = rmp_serde::Serializer::new(Vec::new());
+        let output = crate::path!(.a)
+            .filter(
+                MsgpackSer(ser),
+                &mut rmp_serde::Deserializer::from_read_ref(&msgpack),
+            )
+            .unwrap();
+        assert_eq!(output, [0xc4, 0x02, 0x00, 0xff]);
+        let ser = rmp_serde::Serializer::new(Vec::new());
+        let output = crate::jq::parse(".a")
+            .unwrap()
+            .filter(
+                MsgpackSer(ser),
+                &mut rmp_serde::Deserializer::from_read_ref(&msgpack),
+            )
+            .unwrap();
+        assert_eq!(output, [0xc4, 0x02, 0x00, 0xff]);
+    }
+
+    fn bytes(bytes: &[u8]) -> impl serde::Serialize + '_ {
+        struct Bytes<'a>(&'a [u8]);
+
+        impl serde::Serialize for Bytes<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        Bytes(bytes)
+    }
+}
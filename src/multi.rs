@@ -4,9 +4,8 @@ use serde::de::{
     self,
     value::{MapAccessDeserializer, SeqAccessDeserializer, StrDeserializer},
 };
-use serde_json::value::RawValue;
 
-use crate::FilterChain;
+use crate::{FilterChain, raw::RawValue2};
 
 #[derive(Debug, Clone)]
 pub struct MultiVec<F>(pub Vec<F>);
@@ -20,7 +19,7 @@ where
         D: de::Deserializer<'de>,
         S: de::DeserializeSeed<'de>,
     {
-        let raw = <&'de RawValue as de::Deserialize>::deserialize(deserializer)?;
+        let raw = <RawValue2<'de> as de::Deserialize>::deserialize(deserializer)?;
         seed.deserialize(SeqAccessDeserializer::new(MultiSeqAccess {
             raw,
             filters: self.0.into_iter(),
@@ -30,7 +29,7 @@ where
 }
 
 struct MultiSeqAccess<'de, F> {
-    raw: &'de RawValue,
+    raw: RawValue2<'de>,
     filters: IntoIter<F>,
 }
 
@@ -48,7 +47,7 @@ where
             return Ok(None);
         };
 
-        filter.filter(seed, self.raw).map(Some)
+        self.raw.filter(filter, seed).map(Some)
     }
 }
 
@@ -64,7 +63,7 @@ where
         D: de::Deserializer<'de>,
         S: de::DeserializeSeed<'de>,
     {
-        let raw = <&'de RawValue as de::Deserialize>::deserialize(deserializer)?;
+        let raw = <RawValue2<'de> as de::Deserialize>::deserialize(deserializer)?;
         seed.deserialize(MapAccessDeserializer::new(MultiMapAccess {
             raw,
             filter: None,
@@ -75,7 +74,7 @@ where
 }
 
 struct MultiMapAccess<'de, F> {
-    raw: &'de RawValue,
+    raw: RawValue2<'de>,
     filter: Option<F>,
     filters: IntoIter<(String, F)>,
 }
@@ -101,7 +100,7 @@ where
     where
         V: de::DeserializeSeed<'de>,
     {
-        self.filter.take().unwrap().filter(seed, self.raw)
+        self.raw.filter(self.filter.take().unwrap(), seed)
     }
 }
 
@@ -175,4 +174,30 @@ mod tests {
         assert_eq!(fields["one"].get(), "3");
         assert_eq!(fields["two"].get(), "5");
     }
+
+    #[test]
+    fn reader() {
+        let json = json!({"b": {"c": [2, 3, 4], "d": [5]}}).to_string();
+
+        let filter = hlist!["b", MultiVec(vec![hlist!["c", 1_usize], hlist!["d", 0]])];
+        let mut de = serde_json::Deserializer::from_reader(json.as_bytes());
+        let fields: Vec<u32> = filter.filter(PhantomData::<Vec<u32>>, &mut de).unwrap();
+        assert_eq!(fields, [3, 5]);
+
+        let filter = hlist![
+            "b",
+            MultiMap(vec![
+                ("one".to_string(), hlist!["c", 1_usize]),
+                ("two".to_string(), hlist!["d", 0])
+            ])
+        ];
+        let mut de = serde_json::Deserializer::from_reader(json.as_bytes());
+        let fields: HashMap<String, u32> = filter
+            .filter(PhantomData::<HashMap<String, u32>>, &mut de)
+            .unwrap();
+        assert_eq!(
+            fields,
+            HashMap::from([("one".to_string(), 3), ("two".to_string(), 5)])
+        );
+    }
 }
@@ -2,7 +2,10 @@ use std::marker::PhantomData;
 
 use serde::de;
 
-use crate::FilterChain;
+use crate::{
+    FilterChain,
+    track::{self, Segment},
+};
 
 impl<'de> FilterChain<'de> for String {
     fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
@@ -30,9 +33,9 @@ impl<'de> FilterChain<'de> for &str {
     }
 }
 
-struct MapVisitor<'a, S> {
-    head: &'a str,
-    next: S,
+pub(crate) struct MapVisitor<'a, S> {
+    pub(crate) head: &'a str,
+    pub(crate) next: S,
 }
 
 impl<'de, S> de::Visitor<'de> for MapVisitor<'_, S>
@@ -55,7 +58,8 @@ where
             };
 
             if found {
-                break Some(map.next_value_seed(next)?);
+                let value = map.next_value_seed(next);
+                break Some(track::at(value, || Segment::Key(head.to_owned()))?);
             }
 
             map.next_value::<de::IgnoredAny>()?;
@@ -74,7 +78,7 @@ where
 }
 
 #[derive(Clone, Copy)]
-struct FieldVisitor<'a>(&'a str);
+pub(crate) struct FieldVisitor<'a>(pub(crate) &'a str);
 
 impl<'de> de::Visitor<'de> for FieldVisitor<'_> {
     type Value = bool;
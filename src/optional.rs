@@ -0,0 +1,118 @@
+use crate::{
+    FilterChain, TakeWrapper, or_null,
+    raw::{RawDeserializeSeed, WithRawValue},
+    track,
+};
+use serde::de::{self, DeserializeSeed};
+
+/// jq's `?`: a missing key or a value of the wrong type is absent rather than an error.
+///
+/// Absent values are `null` to the seed, and skipped inside [`Map`](crate::map::Map).
+/// Malformed input is still an error.
+#[derive(Clone, Copy, Debug)]
+pub struct Optional<F>(pub F);
+
+impl<'de, F> FilterChain<'de> for Optional<F>
+where
+    F: FilterChain<'de>,
+{
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        or_null(self, seed, deserializer)
+    }
+
+    fn filter_opt<D, S>(self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        WithRawValue(OptionalSeed {
+            filter: self.0,
+            seed,
+        })
+        .deserialize(deserializer)
+    }
+}
+
+struct OptionalSeed<F, S> {
+    filter: F,
+    seed: S,
+}
+
+impl<'de, F, S> RawDeserializeSeed<'de> for OptionalSeed<F, S>
+where
+    F: FilterChain<'de>,
+    S: DeserializeSeed<'de>,
+{
+    type Value = Option<S::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de> + Clone,
+    {
+        let mut seed = TakeWrapper(Some(self.seed));
+        match self.filter.filter_opt(&mut seed, deserializer) {
+            Ok(val) => Ok(val),
+            // the raw value is well formed, so the filter did not find what it was looking for.
+            Err(_) if seed.0.is_some() => {
+                track::clear();
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::json;
+
+    use super::Optional;
+    use crate::{FilterChain, hlist, map::Map};
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn optional() {
+        let json = json!({ "a": [{"b": 1}, {"c": 2}, 3, {"b": 4}], "d": "x" }).to_string();
+
+        let v: Option<u32> = extract_json_path(&json, hlist![Optional("x")]).unwrap();
+        assert_eq!(v, None);
+        let v: Option<u32> = extract_json_path(&json, hlist![Optional(hlist!["d", 0])]).unwrap();
+        assert_eq!(v, None);
+        let v: Option<&str> = extract_json_path(&json, hlist![Optional("d")]).unwrap();
+        assert_eq!(v, Some("x"));
+        let v: Option<u32> = extract_json_path(&json, hlist!["a", 1, Optional("b")]).unwrap();
+        assert_eq!(v, None);
+        let v: Vec<u32> = extract_json_path(&json, hlist!["a", Map(Optional("b"))]).unwrap();
+        assert_eq!(v, [1, 4]);
+
+        // the seed is not optional.
+        let err = extract_json_path::<_, u32>(&json, hlist![Optional("x")]).unwrap_err();
+        assert!(err.to_string().contains("invalid type: null"), "{err}");
+        let err = extract_json_path::<_, Option<u32>>(&json, hlist![Optional("d")]).unwrap_err();
+        assert!(err.to_string().contains("invalid type: string"), "{err}");
+
+        let malformed = r#"{ "a": [{"b": 1}, {"b": }] }"#;
+        let err = extract_json_path::<_, Vec<u32>>(malformed, hlist!["a", Map(Optional("b"))])
+            .unwrap_err();
+        assert!(err.to_string().contains("expected value"), "{err}");
+        let err =
+            extract_json_path::<_, Option<u32>>(malformed, hlist![Optional("x")]).unwrap_err();
+        assert!(err.to_string().contains("expected value"), "{err}");
+    }
+}
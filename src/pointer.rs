@@ -0,0 +1,275 @@
+use std::{fmt, str::FromStr};
+
+use serde::de;
+
+use crate::{FilterChain, Iter, list::ListVisitor, obj::MapVisitor};
+
+/// An RFC 6901 JSON Pointer, such as `/b/c/1`.
+///
+/// Each reference token selects an object member, or an array element if the token is
+/// an array index and the value is an array.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct JsonPointer(Vec<String>);
+
+impl JsonPointer {
+    pub fn parse(pointer: &str) -> Result<Self, ParseError> {
+        if pointer.is_empty() {
+            return Ok(Self::default());
+        }
+        let Some(pointer) = pointer.strip_prefix('/') else {
+            return Err(ParseError::MissingSlash);
+        };
+
+        let mut tokens = Vec::new();
+        let mut offset = 1;
+        for raw in pointer.split('/') {
+            let mut token = String::with_capacity(raw.len());
+            let mut chars = raw.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '~' => match chars.next() {
+                        Some((_, '0')) => token.push('~'),
+                        Some((_, '1')) => token.push('/'),
+                        _ => return Err(ParseError::InvalidEscape { offset: offset + i }),
+                    },
+                    c => token.push(c),
+                }
+            }
+            tokens.push(token);
+            offset += raw.len() + 1;
+        }
+        Ok(Self(tokens))
+    }
+
+    /// The unescaped reference tokens.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl FromStr for JsonPointer {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.0 {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> FilterChain<'de> for &JsonPointer {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        Iter(self.tokens().map(Token)).filter(seed, deserializer)
+    }
+}
+
+impl<'de> FilterChain<'de> for JsonPointer {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        (&self).filter(seed, deserializer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    MissingSlash,
+    InvalidEscape { offset: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingSlash => f.write_str("JSON pointer must start with `/`"),
+            ParseError::InvalidEscape { offset } => {
+                write!(
+                    f,
+                    "invalid escape at offset {offset}, expected `~0` or `~1`"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A single reference token, which applies to either a map or a sequence.
+#[derive(Clone, Copy)]
+struct Token<'a>(&'a str);
+
+impl Token<'_> {
+    /// `0` or a digit sequence without leading zeros.
+    fn array_index(self) -> Option<usize> {
+        let token = self.0;
+        let digits = token.bytes().all(|b| b.is_ascii_digit());
+        if token.is_empty() || !digits || (token.starts_with('0') && token.len() > 1) {
+            return None;
+        }
+        token.parse().ok()
+    }
+}
+
+impl<'de> FilterChain<'de> for Token<'_> {
+    fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        deserializer.deserialize_any(TokenVisitor {
+            head: self,
+            next: seed,
+        })
+    }
+}
+
+struct TokenVisitor<'a, S> {
+    head: Token<'a>,
+    next: S,
+}
+
+impl<'de, S> de::Visitor<'de> for TokenVisitor<'_, S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map or sequence containing element {:?}", self.head.0)
+    }
+
+    #[inline]
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        MapVisitor {
+            head: self.head.0,
+            next: self.next,
+        }
+        .visit_map(map)
+    }
+
+    #[inline]
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        if self.head.0 == "-" {
+            return Err(de::Error::custom(
+                "`-` refers to the nonexistent element after the last array element",
+            ));
+        }
+        let Some(head) = self.head.array_index() else {
+            return Err(de::Error::custom(format_args!(
+                "invalid array index `{}`",
+                self.head.0
+            )));
+        };
+        ListVisitor {
+            head,
+            next: self.next,
+        }
+        .visit_seq(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::{Value, json};
+
+    use crate::{
+        FilterChain,
+        pointer::{JsonPointer, ParseError},
+    };
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn rfc_examples() {
+        let json = json!({
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "c%d": 2,
+            "e^f": 3,
+            "g|h": 4,
+            "i\\j": 5,
+            "k\"l": 6,
+            " ": 7,
+            "m~n": 8
+        });
+        let text = json.to_string();
+
+        for (pointer, expected) in [
+            ("", json.clone()),
+            ("/foo", json!(["bar", "baz"])),
+            ("/foo/0", json!("bar")),
+            ("/", json!(0)),
+            ("/a~1b", json!(1)),
+            ("/c%d", json!(2)),
+            ("/e^f", json!(3)),
+            ("/g|h", json!(4)),
+            ("/i\\j", json!(5)),
+            ("/k\"l", json!(6)),
+            ("/ ", json!(7)),
+            ("/m~0n", json!(8)),
+        ] {
+            let filter = JsonPointer::parse(pointer).unwrap();
+            assert_eq!(filter.to_string(), pointer);
+            let actual: Value = extract_json_path(&text, filter).unwrap();
+            assert_eq!(actual, expected, "{pointer}");
+        }
+    }
+
+    #[test]
+    fn numeric_tokens() {
+        let json = json!({ "b": {"c": [2, 3, 4], "1": "one"}, "d": [{"1": 5}] }).to_string();
+
+        let value: Value =
+            extract_json_path(&json, "/b/1".parse::<JsonPointer>().unwrap()).unwrap();
+        assert_eq!(value, json!("one"));
+
+        let value: Value =
+            extract_json_path(&json, "/b/c/1".parse::<JsonPointer>().unwrap()).unwrap();
+        assert_eq!(value, json!(3));
+
+        let value: Value =
+            extract_json_path(&json, "/d/0/1".parse::<JsonPointer>().unwrap()).unwrap();
+        assert_eq!(value, json!(5));
+
+        for pointer in ["/b/c/01", "/b/c/-", "/b/c/x", "/b/c/3"] {
+            let filter = JsonPointer::parse(pointer).unwrap();
+            extract_json_path::<_, Value>(&json, filter).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(JsonPointer::parse("b"), Err(ParseError::MissingSlash));
+        assert_eq!(
+            JsonPointer::parse("/a/b~2"),
+            Err(ParseError::InvalidEscape { offset: 4 })
+        );
+        assert_eq!(
+            JsonPointer::parse("/a~"),
+            Err(ParseError::InvalidEscape { offset: 2 })
+        );
+    }
+}
@@ -1,42 +1,865 @@
-use std::marker::PhantomData;
+use std::{cmp::Ordering, fmt, marker::PhantomData};
 
-use serde::de::DeserializeSeed;
+use serde::de::{self, DeserializeSeed};
 
-use crate::FilterChain;
+use crate::{
+    FilterChain,
+    obj::FieldVisitor,
+    raw::{RawDeserializeSeed, WithRawValue},
+};
 
+macro_rules! comparison {
+    ($(#[$attr:meta])* $name:ident, $bound:ident, |$actual:ident, $value:ident| $cmp:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name<F, T, S = PhantomData<T>> {
+            filter: F,
+            seed: S,
+            value: T,
+        }
+
+        impl<F, T> $name<F, T> {
+            pub fn new(filter: F, value: T) -> Self {
+                Self {
+                    filter,
+                    seed: PhantomData,
+                    value,
+                }
+            }
+        }
+
+        impl<'de, F, T, S> FilterPredicate<'de> for $name<F, T, S>
+        where
+            F: FilterChain<'de>,
+            S: DeserializeSeed<'de, Value: $bound<T>>,
+        {
+            fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                let $actual = self.filter.filter(self.seed, deserializer)?;
+                let $value = self.value;
+                Ok($cmp)
+            }
+        }
+    };
+}
+
+comparison!(NotEq, PartialEq, |actual, value| actual != value);
+comparison!(Eq, PartialEq, |actual, value| actual == value);
+comparison!(
+    /// `actual < value`. Use [`Number`] to compare JSON numbers of any representation.
+    Lt, PartialOrd, |actual, value| actual < value
+);
+comparison!(
+    /// `actual <= value`. Use [`Number`] to compare JSON numbers of any representation.
+    Le, PartialOrd, |actual, value| actual <= value
+);
+comparison!(
+    /// `actual > value`. Use [`Number`] to compare JSON numbers of any representation.
+    Gt, PartialOrd, |actual, value| actual > value
+);
+comparison!(
+    /// `actual >= value`. Use [`Number`] to compare JSON numbers of any representation.
+    Ge, PartialOrd, |actual, value| actual >= value
+);
+
+pub trait FilterPredicate<'de> {
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>;
+}
+
+impl<'de, P> FilterPredicate<'de> for Box<P>
+where
+    P: FilterPredicate<'de>,
+{
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        (*self).filter(deserializer)
+    }
+}
+
+/// Holds if both predicates hold. The second one is only run if the first one holds.
 #[derive(Debug, Clone, Copy)]
-pub struct NotEq<F, T, S = PhantomData<T>> {
-    filter: F,
-    seed: S,
-    value: T,
+pub struct And<A, B>(pub A, pub B);
+
+impl<'de, A, B> FilterPredicate<'de> for And<A, B>
+where
+    A: FilterPredicate<'de>,
+    B: FilterPredicate<'de>,
+{
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        WithRawValue(Both {
+            first: self.0,
+            second: self.1,
+            short_circuit: false,
+        })
+        .deserialize(deserializer)
+    }
+}
+
+/// Holds if either predicate holds. The second one is only run if the first one does not.
+#[derive(Debug, Clone, Copy)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<'de, A, B> FilterPredicate<'de> for Or<A, B>
+where
+    A: FilterPredicate<'de>,
+    B: FilterPredicate<'de>,
+{
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        WithRawValue(Both {
+            first: self.0,
+            second: self.1,
+            short_circuit: true,
+        })
+        .deserialize(deserializer)
+    }
+}
+
+/// Runs both predicates against the buffered value, unless the first one returns
+/// `short_circuit`.
+struct Both<A, B> {
+    first: A,
+    second: B,
+    short_circuit: bool,
 }
 
-impl<F, T> NotEq<F, T> {
-    pub fn new(filter: F, value: T) -> Self {
-        Self {
-            filter,
-            seed: PhantomData,
-            value,
+impl<'de, A, B> RawDeserializeSeed<'de> for Both<A, B>
+where
+    A: FilterPredicate<'de>,
+    B: FilterPredicate<'de>,
+{
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: de::Deserializer<'de> + Clone,
+    {
+        if self.first.filter(deserializer.clone())? == self.short_circuit {
+            return Ok(self.short_circuit);
         }
+        self.second.filter(deserializer)
     }
 }
 
-impl<'de, F, T, S> FilterPredicate<'de> for NotEq<F, T, S>
+/// Holds if the predicate does not.
+#[derive(Debug, Clone, Copy)]
+pub struct Not<P>(pub P);
+
+impl<'de, P> FilterPredicate<'de> for Not<P>
 where
-    F: FilterChain<'de>,
-    S: DeserializeSeed<'de, Value: PartialEq<T>>,
+    P: FilterPredicate<'de>,
 {
     fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
     where
         D: serde::de::Deserializer<'de>,
     {
-        let actual = self.filter.filter(self.seed, deserializer)?;
-        Ok(actual != self.value)
+        self.0.filter(deserializer).map(|p| !p)
     }
 }
 
-pub trait FilterPredicate<'de> {
+/// Holds if the value is a map containing the key. Other values have no keys.
+#[derive(Debug, Clone, Copy)]
+pub struct Has<K>(pub K);
+
+impl<'de, K> FilterPredicate<'de> for Has<K>
+where
+    K: AsRef<str>,
+{
     fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
     where
-        D: serde::de::Deserializer<'de>;
+        D: serde::de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(HasVisitor(self.0.as_ref()))
+    }
+}
+
+/// Holds if the value is a sequence with more than `n` elements.
+#[derive(Debug, Clone, Copy)]
+pub struct HasIndex(pub usize);
+
+impl<'de> FilterPredicate<'de> for HasIndex {
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(HasIndexVisitor(self.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonType {
+    Object,
+    Array,
+    String,
+    Number,
+    Bool,
+    Null,
+}
+
+/// Holds if the value has the type.
+#[derive(Debug, Clone, Copy)]
+pub struct IsType(pub JsonType);
+
+impl<'de> FilterPredicate<'de> for IsType {
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        Ok(deserializer.deserialize_any(TypeVisitor)? == self.0)
+    }
+}
+
+/// Holds if the value is `null`, like `IsType(JsonType::Null)`.
+#[derive(Debug, Clone, Copy)]
+pub struct IsNull;
+
+impl<'de> FilterPredicate<'de> for IsNull {
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        IsType(JsonType::Null).filter(deserializer)
+    }
+}
+
+macro_rules! string_test {
+    ($(#[$attr:meta])* $name:ident, |$actual:ident, $pattern:ident| $test:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name<F, P> {
+            filter: F,
+            pattern: P,
+        }
+
+        impl<F, P> $name<F, P> {
+            pub fn new(filter: F, pattern: P) -> Self {
+                Self { filter, pattern }
+            }
+        }
+
+        impl<'de, F, P> FilterPredicate<'de> for $name<F, P>
+        where
+            F: FilterChain<'de>,
+            P: AsRef<str>,
+        {
+            fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                let $pattern = self.pattern.as_ref();
+                self.filter
+                    .filter(StrTest(|$actual: &str| $test), deserializer)
+            }
+        }
+    };
+}
+
+string_test!(
+    /// Holds if the string contains the pattern. Other values are an error.
+    Contains, |actual, pattern| actual.contains(pattern)
+);
+string_test!(
+    /// Holds if the string starts with the pattern. Other values are an error.
+    StartsWith, |actual, pattern| actual.starts_with(pattern)
+);
+string_test!(
+    /// Holds if the string ends with the pattern. Other values are an error.
+    EndsWith, |actual, pattern| actual.ends_with(pattern)
+);
+
+/// Holds if the regex matches somewhere in the string. Other values are an error.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct Matches<F> {
+    filter: F,
+    regex: regex::Regex,
+}
+
+#[cfg(feature = "regex")]
+impl<F> Matches<F> {
+    pub fn new(filter: F, regex: regex::Regex) -> Self {
+        Self { filter, regex }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<'de, F> FilterPredicate<'de> for Matches<F>
+where
+    F: FilterChain<'de>,
+{
+    fn filter<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let regex = &self.regex;
+        self.filter
+            .filter(StrTest(|actual: &str| regex.is_match(actual)), deserializer)
+    }
+}
+
+/// Runs the test on a string, borrowed from the input when it can be.
+struct StrTest<T>(T);
+
+impl<'de, T> DeserializeSeed<'de> for StrTest<T>
+where
+    T: FnOnce(&str) -> bool,
+{
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, T> de::Visitor<'de> for StrTest<T>
+where
+    T: FnOnce(&str) -> bool,
+{
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a string")
+    }
+
+    // `visit_borrowed_str` and `visit_string` forward here.
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<bool, E> {
+        Ok((self.0)(v))
+    }
+}
+
+/// The scalars and the containers a visitor does not look into, skipped as `$value`.
+macro_rules! visit_others {
+    ($value:expr; $($container:ident)*) => {
+        fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_str<E: de::Error>(self, _: &str) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok($value)
+        }
+
+        $(visit_others!(@$container $value);)*
+    };
+    (@seq $value:expr) => {
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+            Ok($value)
+        }
+    };
+    (@map $value:expr) => {
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            while map.next_entry::<de::IgnoredAny, de::IgnoredAny>()?.is_some() {}
+            Ok($value)
+        }
+    };
+}
+
+struct HasVisitor<'a>(&'a str);
+
+impl<'de> de::Visitor<'de> for HasVisitor<'_> {
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<bool, A::Error> {
+        let mut has = false;
+        while let Some(found) = map.next_key_seed(FieldVisitor(self.0))? {
+            map.next_value::<de::IgnoredAny>()?;
+            has |= found;
+        }
+        Ok(has)
+    }
+
+    visit_others!(false; seq);
+}
+
+struct HasIndexVisitor(usize);
+
+impl<'de> de::Visitor<'de> for HasIndexVisitor {
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<bool, A::Error> {
+        let mut len = 0;
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {
+            len += 1;
+        }
+        Ok(len > self.0)
+    }
+
+    visit_others!(false; map);
+}
+
+struct TypeVisitor;
+
+impl<'de> de::Visitor<'de> for TypeVisitor {
+    type Value = JsonType;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<JsonType, E> {
+        Ok(JsonType::Bool)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<JsonType, E> {
+        Ok(JsonType::Number)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<JsonType, E> {
+        Ok(JsonType::Number)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<JsonType, E> {
+        Ok(JsonType::Number)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<JsonType, E> {
+        Ok(JsonType::String)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<JsonType, E> {
+        Ok(JsonType::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<JsonType, E> {
+        Ok(JsonType::Null)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<JsonType, A::Error> {
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+        Ok(JsonType::Array)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<JsonType, A::Error> {
+        while map
+            .next_entry::<de::IgnoredAny, de::IgnoredAny>()?
+            .is_some()
+        {}
+        Ok(JsonType::Object)
+    }
+}
+
+/// A JSON number, compared by value whether it is a `u64`, an `i64` or an `f64`.
+///
+/// `1` equals `1.0`, and `u64::MAX` is greater than `u64::MAX as f64`, which rounds up.
+#[derive(Debug, Clone, Copy)]
+pub struct Number(N);
+
+#[derive(Debug, Clone, Copy)]
+enum N {
+    // holds every u64 and every i64.
+    Int(i128),
+    Float(f64),
+}
+
+macro_rules! from_int {
+    ($($ty:ty),*) => {
+        $(impl From<$ty> for Number {
+            fn from(n: $ty) -> Self {
+                Number(N::Int(n as i128))
+            }
+        })*
+    };
+}
+
+from_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl From<f32> for Number {
+    fn from(n: f32) -> Self {
+        Number(N::Float(n.into()))
+    }
+}
+
+impl From<f64> for Number {
+    fn from(n: f64) -> Self {
+        Number(N::Float(n))
+    }
+}
+
+impl From<&serde_json::Number> for Number {
+    fn from(n: &serde_json::Number) -> Self {
+        match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => n.into(),
+            (_, Some(n)) => n.into(),
+            _ => n.as_f64().unwrap_or(f64::NAN).into(),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            N::Int(n) => n.fmt(f),
+            N::Float(n) => n.fmt(f),
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.0, other.0) {
+            (N::Int(a), N::Int(b)) => Some(a.cmp(&b)),
+            (N::Float(a), N::Float(b)) => a.partial_cmp(&b),
+            (N::Int(a), N::Float(b)) => cmp_int_float(a, b),
+            (N::Float(a), N::Int(b)) => cmp_int_float(b, a).map(Ordering::reverse),
+        }
+    }
+}
+
+/// Compares without rounding the integer to a float.
+fn cmp_int_float(int: i128, float: f64) -> Option<Ordering> {
+    // floats this large are beyond any u64 or i64, and `as` would saturate.
+    const LIMIT: f64 = 18446744073709551616.0;
+    if float.is_nan() {
+        return None;
+    }
+    if float >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float <= -LIMIT {
+        return Some(Ordering::Greater);
+    }
+
+    let trunc = float.trunc();
+    Some(int.cmp(&(trunc as i128)).then(trunc.total_cmp(&float)))
+}
+
+impl<'de> de::Deserialize<'de> for Number {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+struct NumberVisitor;
+
+impl de::Visitor<'_> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a number")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Number, E> {
+        Ok(v.into())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Number, E> {
+        Ok(v.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use serde_json::{json, value::RawValue};
+
+    use crate::{FilterChain, hlist};
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn comparisons() {
+        use super::{Eq, FilterPredicate, Ge, Gt, Lt, Number};
+        use crate::{map::Map, select::Select};
+
+        fn names<'de, P>(json: &'de str, predicate: P) -> Vec<&'de str>
+        where
+            P: FilterPredicate<'de> + Clone,
+        {
+            let filter = hlist!["traceEvents", Map(hlist![Select(predicate), "name"])];
+            extract_json_path(json, filter).unwrap()
+        }
+
+        let json = json!({ "traceEvents": [
+            {"name": "a", "dur": 999},
+            {"name": "b", "dur": 1000.5},
+            {"name": "c", "dur": 1000},
+            {"name": "d", "dur": -3},
+            {"name": "e", "dur": u64::MAX},
+        ]})
+        .to_string();
+
+        assert_eq!(names(&json, Gt::new("dur", Number::from(1000))), ["b", "e"]);
+        assert_eq!(
+            names(&json, Ge::new("dur", Number::from(1000.0))),
+            ["b", "c", "e"]
+        );
+        assert_eq!(
+            names(&json, Lt::new("dur", Number::from(1000.0))),
+            ["a", "d"]
+        );
+        assert_eq!(names(&json, Lt::new("dur", Number::from(-2.5))), ["d"]);
+        assert_eq!(names(&json, Eq::new("dur", Number::from(1e3))), ["c"]);
+        assert_eq!(names(&json, Eq::new("name", "c".to_string())), ["c"]);
+
+        assert!(Number::from(u64::MAX) > Number::from(-1i64));
+        assert!(Number::from(u64::MAX) < Number::from(u64::MAX as f64));
+        assert!(Number::from(9007199254740993u64) > Number::from(9007199254740992.0));
+        assert!(Number::from(-1i64) > Number::from(-1.5));
+        assert!(Number::from(0) == Number::from(-0.0));
+        assert!(Number::from(f64::NAN) != Number::from(f64::NAN));
+
+        let filter = hlist!["traceEvents", Map(Select(Gt::new("name", Number::from(1))))];
+        let err = extract_json_path::<_, Vec<&RawValue>>(&json, filter).unwrap_err();
+        assert!(err.to_string().contains("expected a number"), "{err}");
+    }
+
+    #[test]
+    fn conditions() {
+        use super::{And, Eq, Gt, Not, NotEq, Number, Or};
+        use crate::{map::Map, select::Select};
+
+        let json = json!({ "traceEvents": [
+            {"name": "a", "ph": "X", "dur": 2000},
+            {"name": "b", "ph": "B", "dur": 2000},
+            {"name": "c", "ph": "B", "dur": 10},
+            {"name": "d", "ph": "E", "dur": {"us": 5}},
+        ]})
+        .to_string();
+
+        // `.ph != "X" and .ph != "E" and .dur > 1000`, on a reader so the values are buffered
+        // as owned. `.dur > 1000` fails on "d", so it must not run once `.ph != "E"` does not hold.
+        let not_x = NotEq::new("ph", "X".to_string());
+        let not_e = NotEq::new("ph", "E".to_string());
+        let long = Gt::new("dur", Number::from(1000));
+        let filter = And(And(not_x, not_e), long);
+        let filter = hlist!["traceEvents", Map(hlist![Select(filter), "name"])];
+        let names: Vec<String> = filter
+            .filter(
+                PhantomData::<Vec<String>>,
+                &mut serde_json::Deserializer::from_reader(json.as_bytes()),
+            )
+            .unwrap();
+        assert_eq!(names, ["b"]);
+
+        // and once `.ph == "E"` holds.
+        let e = Eq::new("ph", "E".to_string());
+        let filter = Or(e, Not(Gt::new("dur", Number::from(1000))));
+        let filter = hlist!["traceEvents", Map(hlist![Select(filter), "name"])];
+        let names: Vec<String> = filter
+            .filter(
+                PhantomData::<Vec<String>>,
+                &mut serde_json::Deserializer::from_reader(json.as_bytes()),
+            )
+            .unwrap();
+        assert_eq!(names, ["c", "d"]);
+
+        let names: Vec<&str> = extract_json_path(
+            &json,
+            crate::path!(.traceEvents[] | select(.ph == "B" and (.name == "c" | not)) | .name),
+        )
+        .unwrap();
+        assert_eq!(names, ["b"]);
+        let names: Vec<&str> = extract_json_path(
+            &json,
+            crate::path!(.traceEvents[] | select(.name == "d" or (.dur) < 1000 | not) | .name),
+        )
+        .unwrap();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn existence() {
+        use super::{FilterPredicate, Has, HasIndex, IsNull, IsType, JsonType};
+        use crate::{map_select::MapSelect, select::Select};
+
+        let json = json!({ "traceEvents": [
+            {"name": "a", "args": {}},
+            {"name": "b"},
+            {"name": "c", "args": null},
+            "d",
+        ]})
+        .to_string();
+        let events: Vec<serde_json::Value> =
+            extract_json_path(&json, hlist!["traceEvents", MapSelect(Has("args"))]).unwrap();
+        assert_eq!(
+            events,
+            [
+                json!({"name": "a", "args": {}}),
+                json!({"name": "c", "args": null})
+            ]
+        );
+
+        // streaming, so the rest of the map must still be read
+        let filter = hlist![Select(Has("a")), "b"];
+        let mut de = serde_json::Deserializer::from_str(r#"{"a": [1, {"x": 2}], "b": 3}"#);
+        assert_eq!(filter.filter(PhantomData::<u32>, &mut de).unwrap(), 3);
+        de.end().unwrap();
+
+        fn check<'de>(json: &'de str, predicate: impl FilterPredicate<'de>) -> bool {
+            predicate
+                .filter(&mut serde_json::Deserializer::from_str(json))
+                .unwrap()
+        }
+        // derived structs read the key and the value of an entry in separate calls
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Kept {
+            a: u32,
+            c: u32,
+        }
+        let entries = r#"{"a": 1, "b": null, "c": 2}"#;
+        let kept: Kept = extract_json_path(entries, MapSelect(IsType(JsonType::Number))).unwrap();
+        assert_eq!(kept, Kept { a: 1, c: 2 });
+
+        assert!(check(r#"[1, 2]"#, HasIndex(1)));
+        assert!(!check(r#"[1, 2]"#, HasIndex(2)));
+        assert!(!check(r#"{"1": 2}"#, HasIndex(1)));
+        assert!(!check(r#"[["a"]]"#, Has("a")));
+
+        let types = [
+            (r#"{"a": 1}"#, JsonType::Object),
+            ("[]", JsonType::Array),
+            (r#""a""#, JsonType::String),
+            ("-1.5", JsonType::Number),
+            ("false", JsonType::Bool),
+            ("null", JsonType::Null),
+        ];
+        for (json, ty) in types {
+            for (_, other) in types {
+                assert_eq!(check(json, IsType(other)), ty == other, "{json} {other:?}");
+            }
+            assert_eq!(check(json, IsNull), ty == JsonType::Null);
+        }
+
+        let names: Vec<&str> = extract_json_path(
+            &json,
+            crate::path!(.traceEvents[] | select(has("args") | not) | .name?),
+        )
+        .unwrap();
+        assert_eq!(names, ["b"]);
+    }
+
+    #[test]
+    fn strings() {
+        use super::{Contains, EndsWith, StartsWith};
+        use crate::{map::Map, map_select::MapSelect, select::Select};
+
+        let json = r#"{"traceEvents": [
+            {"name": "OptimizeModule"},
+            {"name": "RunPass"},
+            {"name": "RunLoopPass"},
+            {"name": "Run\u0050ass"}
+        ]}"#;
+
+        let names: Vec<&str> = extract_json_path(
+            json,
+            hlist![
+                "traceEvents",
+                Map(hlist![Select(StartsWith::new("name", "Optimize")), "name"])
+            ],
+        )
+        .unwrap();
+        assert_eq!(names, ["OptimizeModule"]);
+
+        // escaped and read from a reader, so the strings are not borrowed
+        let filter = hlist![
+            "traceEvents",
+            Map(hlist![Select(EndsWith::new("name", "Pass")), "name"])
+        ];
+        let names: Vec<String> = filter
+            .filter(
+                PhantomData::<Vec<String>>,
+                &mut serde_json::Deserializer::from_reader(json.as_bytes()),
+            )
+            .unwrap();
+        assert_eq!(names, ["RunPass", "RunLoopPass", "RunPass"]);
+
+        let filter = hlist!["traceEvents", MapSelect(EndsWith::new("name", "Module"))];
+        let events: Vec<serde_json::Value> = extract_json_path(json, filter).unwrap();
+        assert_eq!(events, [json!({"name": "OptimizeModule"})]);
+
+        let names: Vec<String> = extract_json_path(
+            json,
+            crate::path!(.traceEvents[] | select(.name | contains("Loop") | not) | .name),
+        )
+        .unwrap();
+        assert_eq!(names, ["OptimizeModule", "RunPass", "RunPass"]);
+
+        // `|` binds loosest, so both tests apply to `.name`
+        let names: Vec<String> = extract_json_path(
+            json,
+            crate::path!(.traceEvents[] | select(.name | endswith("Module") or contains("Loop")) | .name),
+        )
+        .unwrap();
+        assert_eq!(names, ["OptimizeModule", "RunLoopPass"]);
+
+        let err = extract_json_path::<_, Vec<serde_json::Value>>(
+            r#"[{"name": 1}]"#,
+            MapSelect(Contains::new("name", "a")),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected a string"), "{err}");
+
+        #[cfg(feature = "regex")]
+        {
+            let regex = regex::Regex::new("^Run.*Pass$").unwrap();
+            let filter = hlist!["traceEvents", MapSelect(super::Matches::new("name", regex))];
+            let events: Vec<serde_json::Value> = extract_json_path(json, filter).unwrap();
+            assert_eq!(events.len(), 3);
+        }
+    }
 }
@@ -1,9 +1,38 @@
-use std::{marker::PhantomData, ops::Deref};
-
-use serde::{Deserializer, de};
+//! Capturing a value once, to be read as many times as a filter needs.
+//!
+//! JSON is captured as a [`RawValue`] through serde_json's own `Deserialize` for `&RawValue`,
+//! rather than by building one from a string. That impl follows a protocol private to
+//! serde_json, of which this module relies on the following:
+//!
+//! - It calls `deserialize_newtype_struct` with a reserved name. serde_json's deserializers
+//!   answer with `visit_map`, over a map whose only key is that name, as a string. `Capture`
+//!   takes whatever name it is given as the token, and buffers any other map as [`Content`].
+//! - The value of that entry is the raw text. It is visited with `visit_borrowed_str` when the
+//!   input is borrowed, and with `visit_str` or `visit_string` otherwise, for instance from
+//!   `Deserializer::from_reader` or a [`serde_json::Value`]. `RawString` copies the text in
+//!   the latter case, into an owned [`RawValue`] validated by [`RawValue::from_string`].
+//! - A value that is not a borrowed raw value is kept aside, and an error is returned to stop
+//!   serde_json's visitor, which would reject it. The error is allocated and then discarded,
+//!   so it is control flow rather than a failure: `capture_json` returns what was kept.
+//!
+//! Every [`RawValue`] is thus made by serde_json, from text it has checked, so no code here is
+//! `unsafe` or assumes how a [`RawValue`] is laid out. A change to the protocol makes captures
+//! fall back to [`Content`], or fail, rather than become unsound. The `protocol` test checks
+//! each step above, so such a change is caught before it reaches the filters.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use serde::{Deserialize, Deserializer, de, forward_to_deserialize_any};
 use serde_json::value::RawValue;
 
-use crate::borrow::Unborrow;
+#[cfg(feature = "cbor")]
+use crate::cbor::RawCbor;
+use crate::{
+    FilterChain,
+    borrow::Unborrow,
+    content::{Content, ContentVisitor, Replay},
+    predicate::FilterPredicate,
+};
 
 pub trait RawDeserializeSeed<'de> {
     type Value;
@@ -24,183 +53,602 @@ where
     where
         D: de::Deserializer<'de>,
     {
-        deserializer.deserialize_newtype_struct(TOKEN, self)
+        match RawValue2::deserialize(deserializer)? {
+            RawValue2::Borrowed(raw) => self.0.deserialize(raw),
+            RawValue2::Owned(raw) => self.0.deserialize(Unborrow(&*raw, PhantomData)),
+            RawValue2::Content(content, human_readable) => {
+                self.0.deserialize(Replay::new(&content, human_readable))
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => self.0.deserialize(raw).map_err(de::Error::custom),
+        }
+        .map_err(de::Error::custom)
     }
 }
 
-impl<'de, F> de::Visitor<'de> for WithRawValue<F>
-where
-    F: RawDeserializeSeed<'de>,
-{
-    type Value = F::Value;
+pub enum RawValue2<'de> {
+    Borrowed(&'de RawValue),
+    Owned(Box<RawValue>),
+    /// A value from a deserializer that cannot capture raw JSON, such as another format, and
+    /// whether that deserializer is human readable.
+    Content(Content<'de>, bool),
+    #[cfg(feature = "cbor")]
+    Cbor(RawCbor<'de>),
+}
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(formatter, "any valid JSON value")
+impl<'de> RawValue2<'de> {
+    /// Passes the value to the seed, borrowed from the input if it was.
+    pub(crate) fn deserialize_into<S>(&self, seed: S) -> Result<S::Value, serde_json::Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        match self {
+            RawValue2::Borrowed(raw) => seed.deserialize(*raw),
+            RawValue2::Owned(raw) => seed.deserialize(Unborrow(&**raw, PhantomData)),
+            RawValue2::Content(content, human_readable) => {
+                seed.deserialize(Replay::new(content, *human_readable))
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => seed.deserialize(*raw).map_err(de::Error::custom),
+        }
     }
 
-    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
-        let value = map.next_key::<RawKey>()?;
-        if value.is_none() {
-            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+    /// Runs the filter on the value, borrowed from the input if it was.
+    pub(crate) fn filter<F, S>(&self, filter: F, seed: S) -> Result<S::Value, serde_json::Error>
+    where
+        F: FilterChain<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        match self {
+            RawValue2::Borrowed(raw) => filter.filter(seed, *raw),
+            RawValue2::Owned(raw) => filter.filter(seed, Unborrow(&**raw, PhantomData)),
+            RawValue2::Content(content, human_readable) => {
+                filter.filter(seed, Replay::new(content, *human_readable))
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => filter.filter(seed, *raw).map_err(de::Error::custom),
         }
-        map.next_value_seed(WithRawValue2(self.0))
     }
-}
 
-struct WithRawValue2<F>(F);
+    /// Runs the predicate on the value, borrowed from the input if it was.
+    pub(crate) fn test<P>(&self, predicate: P) -> Result<bool, serde_json::Error>
+    where
+        P: FilterPredicate<'de>,
+    {
+        match self {
+            RawValue2::Borrowed(raw) => predicate.filter(*raw),
+            RawValue2::Owned(raw) => predicate.filter(Unborrow(&**raw, PhantomData)),
+            RawValue2::Content(content, human_readable) => {
+                predicate.filter(Replay::new(content, *human_readable))
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => predicate.filter(*raw).map_err(de::Error::custom),
+        }
+    }
 
-impl<'de, F> de::DeserializeSeed<'de> for WithRawValue2<F>
-where
-    F: RawDeserializeSeed<'de>,
-{
-    type Value = F::Value;
-    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    /// The value as JSON text. Buffered content is serialized, so bytes become arrays and map
+    /// keys have to be strings or numbers.
+    pub fn json(&self) -> Result<Cow<'_, RawValue>, serde_json::Error> {
+        match self {
+            RawValue2::Borrowed(raw) => Ok(Cow::Borrowed(raw)),
+            RawValue2::Owned(raw) => Ok(Cow::Borrowed(raw)),
+            RawValue2::Content(content, _) => {
+                serde_json::value::to_raw_value(content).map(Cow::Owned)
+            }
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => Content::deserialize(*raw)
+                .map_err(de::Error::custom)
+                .and_then(|content| serde_json::value::to_raw_value(&content))
+                .map(Cow::Owned),
+        }
+    }
+}
+
+/// A raw value is captured through serde_json's own `Deserialize` for `&RawValue`, which only
+/// accepts a string borrowed from the input. `Capture` wraps the deserializer to catch the
+/// string when it is not borrowed, and keeps a copy of it instead. A deserializer that does not
+/// know about raw values answers with the value itself, which is buffered as [`Content`].
+impl<'de> de::Deserialize<'de> for RawValue2<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        deserializer.deserialize_str(self)
+        #[cfg(feature = "cbor")]
+        return {
+            let human_readable = deserializer.is_human_readable();
+            deserializer
+                .deserialize_newtype_struct(crate::cbor::TOKEN, CborProbe { human_readable })
+        };
+        #[cfg(not(feature = "cbor"))]
+        capture_json(deserializer)
     }
 }
 
-impl<'de, F> de::Visitor<'de> for WithRawValue2<F>
+fn capture_json<'de, D>(deserializer: D) -> Result<RawValue2<'de>, D::Error>
 where
-    F: RawDeserializeSeed<'de>,
+    D: de::Deserializer<'de>,
 {
-    type Value = F::Value;
-
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("raw value")
+    let mut found = None;
+    let capture = Capture {
+        human_readable: deserializer.is_human_readable(),
+        de: deserializer,
+        token: "",
+        found: &mut found,
+    };
+    match <&'de RawValue as de::Deserialize>::deserialize(capture) {
+        Ok(raw) => Ok(RawValue2::Borrowed(raw)),
+        Err(err) => found.ok_or(err),
     }
+}
 
-    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
-        self.0
-            .deserialize(Unborrow(cast(s), PhantomData))
-            .map_err(E::custom)
-    }
+/// The visits that buffer the value, for a visitor with a `keep_content` method.
+macro_rules! visit_content {
+    ($($method:ident($($arg:ident: $ty:ty)?);)+) => {
+        $(
+            fn $method<E: de::Error>(self, $($arg: $ty)?) -> Result<Self::Value, E> {
+                let content = ContentVisitor.$method($($arg)?)?;
+                self.keep_content(content)
+            }
+        )+
 
-    fn visit_borrowed_str<E: de::Error>(self, s: &'de str) -> Result<Self::Value, E> {
-        self.0.deserialize(cast(s)).map_err(E::custom)
-    }
+        fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+            let content = ContentVisitor.visit_some(d)?;
+            self.keep_content(content)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            let content = ContentVisitor.visit_seq(seq)?;
+            self.keep_content(content)
+        }
+
+        fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+            let content = ContentVisitor.visit_enum(data)?;
+            self.keep_content(content)
+        }
+    };
+    () => {
+        visit_content! {
+            visit_bool(v: bool);
+            visit_i64(v: i64);
+            visit_u64(v: u64);
+            visit_i128(v: i128);
+            visit_u128(v: u128);
+            visit_f64(v: f64);
+            visit_char(v: char);
+            visit_str(v: &str);
+            visit_borrowed_str(v: &'de str);
+            visit_string(v: String);
+            visit_bytes(v: &[u8]);
+            visit_borrowed_bytes(v: &'de [u8]);
+            visit_byte_buf(v: Vec<u8>);
+            visit_none();
+            visit_unit();
+        }
+    };
 }
 
-fn cast(s: &str) -> &RawValue {
-    // Safety: this is not sound since RawValue layout is not guaranteed.
-    // This however works for now.
-    unsafe { std::mem::transmute::<&str, &RawValue>(s) }
+/// Buffers the rest of a map, after its first key.
+fn rest_of_map<'de, A>(key: Content<'de>, map: &mut A) -> Result<Content<'de>, A::Error>
+where
+    A: de::MapAccess<'de>,
+{
+    let mut entries = vec![(key, map.next_value()?)];
+    while let Some(entry) = map.next_entry()? {
+        entries.push(entry);
+    }
+    Ok(Content::Map(entries))
 }
-fn cast_box(s: Box<str>) -> Box<RawValue> {
-    // Safety: this is not sound since RawValue layout is not guaranteed.
-    // This however works for now.
-    unsafe { std::mem::transmute::<Box<str>, Box<RawValue>>(s) }
+
+/// Asks for a raw CBOR value, which only [`cbor::Deserializer`](crate::cbor::Deserializer)
+/// answers. Other deserializers go on to capture raw JSON.
+#[cfg(feature = "cbor")]
+struct CborProbe {
+    human_readable: bool,
 }
 
-// technically not stable api from serde_json.
-// i don't really care.
-const TOKEN: &str = "$serde_json::private::RawValue";
-struct RawKey;
+#[cfg(feature = "cbor")]
+impl CborProbe {
+    fn keep_content<'de, E>(self, content: Content<'de>) -> Result<RawValue2<'de>, E> {
+        Ok(RawValue2::Content(content, self.human_readable))
+    }
+}
 
-impl<'de> de::Deserialize<'de> for RawKey {
-    fn deserialize<D>(deserializer: D) -> Result<RawKey, D::Error>
-    where
-        D: de::Deserializer<'de>,
-    {
-        struct FieldVisitor;
+#[cfg(feature = "cbor")]
+impl<'de> de::Visitor<'de> for CborProbe {
+    type Value = RawValue2<'de>;
 
-        impl<'de> de::Visitor<'de> for FieldVisitor {
-            type Value = ();
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("raw value")
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        match map.next_key::<Content<'de>>()? {
+            None => self.keep_content(Content::Map(Vec::new())),
+            Some(key) if key.as_str() == Some(crate::cbor::TOKEN) => {
+                map.next_value().map(|raw| RawValue2::Cbor(RawCbor(raw)))
             }
-
-            fn visit_str<E>(self, s: &str) -> Result<(), E>
-            where
-                E: de::Error,
-            {
-                if s == TOKEN {
-                    Ok(())
-                } else {
-                    Err(de::Error::custom("unexpected raw value"))
-                }
+            Some(key) => {
+                let content = rest_of_map(key, &mut map)?;
+                self.keep_content(content)
             }
         }
+    }
 
-        deserializer.deserialize_identifier(FieldVisitor)?;
-        Ok(RawKey)
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        capture_json(d)
     }
+
+    visit_content!();
 }
 
-pub enum RawValue2<'de> {
-    Borrowed(&'de RawValue),
-    Owned(Box<RawValue>),
+/// Forwards to the deserializer, the visitor and the map it was given by serde_json, keeping
+/// aside any value that is not a borrowed raw value.
+struct Capture<'a, 'de, T> {
+    de: T,
+    /// Whether the deserializer is, for values that are buffered.
+    human_readable: bool,
+    /// The name serde_json asks for raw values with, and uses as their only key.
+    token: &'static str,
+    found: &'a mut Option<RawValue2<'de>>,
 }
 
-impl Deref for RawValue2<'_> {
-    type Target = RawValue;
+impl<'de, T> Capture<'_, 'de, T> {
+    // the error stops serde_json from going on with a value it cannot borrow
+    fn keep<V, E: de::Error>(self, value: RawValue2<'de>) -> Result<V, E> {
+        *self.found = Some(value);
+        Err(E::custom("the raw value is not borrowed from the input"))
+    }
 
-    fn deref(&self) -> &Self::Target {
-        match self {
-            RawValue2::Borrowed(v) => v,
-            RawValue2::Owned(v) => v,
-        }
+    fn keep_content<V, E: de::Error>(self, content: Content<'de>) -> Result<V, E> {
+        let human_readable = self.human_readable;
+        self.keep(RawValue2::Content(content, human_readable))
     }
 }
 
-impl<'de> de::Deserialize<'de> for RawValue2<'de> {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: de::Deserializer<'de>,
-    {
-        deserializer.deserialize_newtype_struct(TOKEN, RawValue2Visitor)
+impl<'de, D> Deserializer<'de> for Capture<'_, 'de, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let (token, human_readable) = (self.token, self.human_readable);
+        self.de.deserialize_any(Capture {
+            de: visitor,
+            human_readable,
+            token,
+            found: self.found,
+        })
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let human_readable = self.human_readable;
+        self.de.deserialize_newtype_struct(
+            name,
+            Capture {
+                de: visitor,
+                human_readable,
+                token: name,
+                found: self.found,
+            },
+        )
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
     }
 }
 
-struct RawValue2Visitor;
-impl<'de> de::Visitor<'de> for RawValue2Visitor {
-    type Value = RawValue2<'de>;
+impl<'de, V> de::Visitor<'de> for Capture<'_, 'de, V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(formatter, "any valid JSON value")
+        self.de.expecting(formatter)
     }
 
-    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
-        let value = map.next_key::<RawKey>()?;
-        if value.is_none() {
-            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let (token, human_readable) = (self.token, self.human_readable);
+        let (visitor, map) = (
+            self.de,
+            Capture {
+                de: map,
+                human_readable,
+                token,
+                found: self.found,
+            },
+        );
+        visitor.visit_map(map)
+    }
+
+    visit_content!();
+
+    // how most formats answer the request for a raw value
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        let content = Content::deserialize(d)?;
+        self.keep_content(content)
+    }
+}
+
+/// The map of a raw value has a single entry, keyed by the token. Any other map is buffered.
+impl<'de, A> de::MapAccess<'de> for Capture<'_, 'de, A>
+where
+    A: de::MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some(key) = self.de.next_key::<Content<'de>>()? else {
+            *self.found = Some(RawValue2::Content(
+                Content::Map(Vec::new()),
+                self.human_readable,
+            ));
+            return Ok(None);
+        };
+        if key.as_str() == Some(self.token) {
+            return seed.deserialize(&key).map(Some).map_err(de::Error::custom);
         }
-        map.next_value_seed(RawValue2Visitor2)
+        let content = rest_of_map(key, &mut self.de)?;
+        *self.found = Some(RawValue2::Content(content, self.human_readable));
+        Err(de::Error::custom(
+            "the raw value is not borrowed from the input",
+        ))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.de.next_value_seed(RawString {
+            de: seed,
+            found: &mut *self.found,
+        })
     }
 }
 
-struct RawValue2Visitor2;
+/// The text of a raw value, kept aside when it is not borrowed.
+struct RawString<'a, 'de, T> {
+    de: T,
+    found: &'a mut Option<RawValue2<'de>>,
+}
 
-impl<'de> de::DeserializeSeed<'de> for RawValue2Visitor2 {
-    type Value = RawValue2<'de>;
+impl<'de, S> de::DeserializeSeed<'de> for RawString<'_, 'de, S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
 
-    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_any(self)
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.de.deserialize(RawString {
+            de: deserializer,
+            found: self.found,
+        })
     }
 }
 
-impl<'de> de::Visitor<'de> for RawValue2Visitor2 {
-    type Value = RawValue2<'de>;
+impl<'de, D> Deserializer<'de> for RawString<'_, 'de, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.de.deserialize_any(RawString {
+            de: visitor,
+            found: self.found,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, V> de::Visitor<'de> for RawString<'_, 'de, V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("raw value")
+        self.de.expecting(formatter)
     }
 
-    fn visit_string<E: de::Error>(self, s: String) -> Result<Self::Value, E> {
-        Ok(RawValue2::Owned(cast_box(s.into_boxed_str())))
+    fn visit_borrowed_str<E: de::Error>(self, s: &'de str) -> Result<Self::Value, E> {
+        self.de.visit_borrowed_str(s)
     }
 
     fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
-        Ok(RawValue2::Owned(cast(s).to_owned()))
+        self.visit_string(s.to_owned())
     }
 
-    fn visit_borrowed_str<E: de::Error>(self, s: &'de str) -> Result<Self::Value, E> {
-        Ok(RawValue2::Borrowed(cast(s)))
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Self::Value, E> {
+        *self.found = Some(RawValue2::Owned(
+            RawValue::from_string(s).map_err(E::custom)?,
+        ));
+        Err(E::custom("the raw value is not borrowed from the input"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use serde::{Deserialize, Deserializer, de};
+    use serde_json::{json, value::RawValue};
+
+    use super::RawValue2;
+    use crate::content::Content;
+
+    #[test]
+    fn borrowed_or_owned() {
+        let input = r#"{"a": [1, "b"]}"#;
+        let raw: RawValue2 = serde_json::from_str(input).unwrap();
+        assert!(matches!(raw, RawValue2::Borrowed(_)));
+        assert_eq!(raw.json().unwrap().get(), input);
+
+        let mut de = serde_json::Deserializer::from_reader(input.as_bytes());
+        let raw = RawValue2::deserialize(&mut de).unwrap();
+        assert!(matches!(raw, RawValue2::Owned(_)));
+        assert_eq!(raw.json().unwrap().get(), input);
+
+        let raw = RawValue2::deserialize(json!({"a": [1, "b"]})).unwrap();
+        assert!(matches!(raw, RawValue2::Owned(_)));
+        assert_eq!(raw.json().unwrap().get(), r#"{"a":[1,"b"]}"#);
+
+        // deserializers that cannot capture raw values
+        let values =
+            serde::de::value::MapDeserializer::<_, serde_json::Error>::new([("a", 1)].into_iter());
+        let raw = RawValue2::deserialize(values).unwrap();
+        let RawValue2::Content(content, _) = &raw else {
+            panic!("not buffered");
+        };
+        assert_eq!(
+            content,
+            &Content::Map(vec![(Content::String("a".into()), Content::I64(1))])
+        );
+        assert_eq!(raw.json().unwrap().get(), r#"{"a":1}"#);
+
+        let values = serde::de::value::SeqDeserializer::<_, serde_json::Error>::new(
+            [true, false].into_iter(),
+        );
+        let raw = RawValue2::deserialize(values).unwrap();
+        assert_eq!(raw.json().unwrap().get(), "[true,false]");
+        let raw = RawValue2::deserialize(
+            serde::de::value::StrDeserializer::<serde_json::Error>::new("s"),
+        )
+        .unwrap();
+        assert_eq!(raw.json().unwrap().get(), r#""s""#);
+    }
+
+    /// What serde_json did when asked for a raw value.
+    #[derive(Debug, PartialEq)]
+    enum Step {
+        Key(String),
+        Borrowed(String),
+        Owned(String),
+        End,
+    }
+
+    /// Records the name `&RawValue` asks for, failing once it is known.
+    struct Name<'a>(&'a mut Option<&'static str>);
+
+    impl<'de> Deserializer<'de> for Name<'_> {
+        type Error = serde_json::Error;
+
+        fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a newtype struct"))
+        }
+
+        fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+            self,
+            name: &'static str,
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = Some(name);
+            Err(de::Error::custom("recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    struct Record<'a>(&'a mut Vec<Step>);
+
+    impl<'de> de::Visitor<'de> for Record<'_> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("anything")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+            while let Some(key) = map.next_key::<String>()? {
+                self.0.push(Step::Key(key));
+                map.next_value_seed(Record(&mut *self.0))?;
+            }
+            self.0.push(Step::End);
+            Ok(())
+        }
+
+        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<(), E> {
+            self.0.push(Step::Borrowed(v.into()));
+            Ok(())
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<(), E> {
+            self.0.push(Step::Owned(v.into()));
+            Ok(())
+        }
+    }
+
+    impl<'de> de::DeserializeSeed<'de> for Record<'_> {
+        type Value = ();
+
+        fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+            d.deserialize_any(self)
+        }
+    }
+
+    /// Fails if serde_json stops capturing raw values the way the module docs describe.
+    #[test]
+    fn protocol() {
+        let mut name = None;
+        let _ = <&RawValue>::deserialize(Name(&mut name));
+        let name = name.expect("`&RawValue` no longer asks for a newtype struct");
+
+        let steps = |de: &mut dyn FnMut(Record) -> serde_json::Result<()>| {
+            let mut steps = Vec::new();
+            de(Record(&mut steps)).unwrap();
+            steps
+        };
+        let input = r#"{"a": [1]}"#;
+        assert_eq!(
+            steps(&mut |record| serde_json::Deserializer::from_str(input)
+                .deserialize_newtype_struct(name, record)),
+            [
+                Step::Key(name.into()),
+                Step::Borrowed(input.into()),
+                Step::End
+            ],
+            "the raw value of a borrowed input"
+        );
+        assert_eq!(
+            steps(
+                &mut |record| serde_json::Deserializer::from_reader(input.as_bytes())
+                    .deserialize_newtype_struct(name, record)
+            ),
+            [Step::Key(name.into()), Step::Owned(input.into()), Step::End],
+            "the raw value of a reader"
+        );
+        assert_eq!(
+            steps(&mut |record| json!({"a": [1]}).deserialize_newtype_struct(name, record)),
+            [
+                Step::Key(name.into()),
+                Step::Owned(r#"{"a":[1]}"#.into()),
+                Step::End
+            ],
+            "the raw value of a `Value`"
+        );
+
+        // an owned string is rejected, which `RawString` relies on to stop serde_json
+        let mut de = serde_json::Deserializer::from_reader(input.as_bytes());
+        assert!(<&RawValue>::deserialize(&mut de).is_err());
     }
 }
@@ -5,6 +5,10 @@ use crate::{
 };
 use serde::de::{self, DeserializeSeed};
 
+/// Passes the value on if the predicate holds.
+///
+/// A rejected value is a no match for [`FilterChain::filter_opt`], which [`Map`](crate::map::Map)
+/// skips, and an error otherwise.
 #[derive(Clone, Copy, Debug)]
 pub struct Select<F>(pub F);
 
@@ -13,6 +17,15 @@ where
     F: FilterPredicate<'de>,
 {
     fn filter<D, S>(self, seed: S, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        self.filter_opt(seed, deserializer)?
+            .ok_or_else(|| de::Error::custom("value did not match `select`"))
+    }
+
+    fn filter_opt<D, S>(self, seed: S, deserializer: D) -> Result<Option<S::Value>, D::Error>
     where
         D: serde::de::Deserializer<'de>,
         S: de::DeserializeSeed<'de>,
@@ -24,7 +37,7 @@ where
 impl<'de, F: FilterPredicate<'de>, S: de::DeserializeSeed<'de>> RawDeserializeSeed<'de>
     for Select<(F, S)>
 {
-    type Value = S::Value;
+    type Value = Option<S::Value>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
@@ -36,9 +49,11 @@ impl<'de, F: FilterPredicate<'de>, S: de::DeserializeSeed<'de>> RawDeserializeSe
             .map_err(de::Error::custom)?;
 
         if p {
-            seed.deserialize(deserializer).map_err(de::Error::custom)
+            seed.deserialize(deserializer)
+                .map(Some)
+                .map_err(de::Error::custom)
         } else {
-            Err(de::Error::custom("filtered"))
+            Ok(None)
         }
     }
 }
@@ -274,3 +289,46 @@ impl<'de, F: FilterPredicate<'de>, S: de::DeserializeSeed<'de>> RawDeserializeSe
 //         todo!()
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, marker::PhantomData};
+
+    use serde_json::{json, value::RawValue};
+
+    use super::Select;
+    use crate::{
+        FilterChain, hlist,
+        map::{Map, MapValues},
+        predicate::NotEq,
+    };
+
+    fn extract_json_path<'de, F, T>(json: &'de str, filter: F) -> Result<T, serde_json::Error>
+    where
+        T: serde::Deserialize<'de>,
+        F: FilterChain<'de>,
+    {
+        filter.filter(
+            PhantomData::<T>,
+            &mut serde_json::Deserializer::from_str(json),
+        )
+    }
+
+    #[test]
+    fn select_errors() {
+        let json =
+            json!({ "a": [{"x": 1}, {"x": 2}], "b": {"p": {"x": 1}, "q": {"x": "s"}} }).to_string();
+
+        let filter = hlist!["a", Map(hlist![Select(NotEq::new("x", 1.0))])];
+        let v: Vec<&RawValue> = extract_json_path(&json, filter).unwrap();
+        assert_eq!(v.len(), 1);
+
+        let filter = hlist!["b", MapValues(hlist![Select(NotEq::new("x", 1.0))])];
+        let err = extract_json_path::<_, HashMap<&str, &RawValue>>(&json, filter).unwrap_err();
+        assert!(err.to_string().contains("invalid type: string"), "{err}");
+
+        let filter = hlist!["a", 0, Select(NotEq::new("x", 1.0))];
+        let err = extract_json_path::<_, &RawValue>(&json, filter).unwrap_err();
+        assert!(err.to_string().contains("did not match"), "{err}");
+    }
+}
@@ -0,0 +1,581 @@
+//! Filtering a stream of JSON values, such as newline-delimited JSON.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    marker::PhantomData,
+    ops::{ControlFlow, Deref},
+};
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, SeqAccess, Visitor};
+use serde_json::{
+    Deserializer, StreamDeserializer,
+    de::IoRead,
+    ser::{CompactFormatter, Formatter},
+    value::RawValue,
+};
+
+use crate::{
+    FilterChain,
+    json_ser::{JsonSer, SerWrapper},
+};
+
+/// The record separator that starts every value of an RFC 7464 JSON text sequence.
+const RS: u8 = 0x1e;
+
+/// The top-level values of a reader, each one buffered on its own.
+pub struct Records<R: io::Read> {
+    inner: Inner<R>,
+}
+
+enum Inner<R: io::Read> {
+    Json(StreamDeserializer<'static, IoRead<R>, Box<RawValue>>),
+    Seq(R, Vec<u8>),
+}
+
+impl<R: BufRead> Records<R> {
+    /// Values separated by whitespace, or none at all: NDJSON or concatenated JSON.
+    /// A value that does not parse ends the stream.
+    pub fn new(reader: R) -> Self {
+        Records {
+            inner: Inner::Json(Deserializer::from_reader(reader).into_iter()),
+        }
+    }
+
+    /// An RFC 7464 JSON text sequence, where every value follows a record separator.
+    /// A value that does not parse is an error for that record only.
+    pub fn seq(reader: R) -> Self {
+        Records {
+            inner: Inner::Seq(reader, Vec::new()),
+        }
+    }
+
+    pub fn is_seq(&self) -> bool {
+        matches!(self.inner, Inner::Seq(..))
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Box<RawValue>, serde_json::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (reader, buf) = match &mut self.inner {
+            Inner::Json(values) => return values.next(),
+            Inner::Seq(reader, buf) => (reader, buf),
+        };
+        loop {
+            buf.clear();
+            match reader.read_until(RS, buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(serde_json::Error::io(err))),
+            }
+            let record = buf.strip_suffix(&[RS]).unwrap_or(buf);
+            // before the first separator, or between two in a row
+            if record.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Some(serde_json::from_slice(record));
+        }
+    }
+}
+
+/// Applies the filter to every value, yielding the output of those it matches.
+///
+/// Values are read with [`Records`], or with a [`StreamDeserializer`]:
+///
+/// ```
+/// use serde_path::stream::{Records, filter_each};
+///
+/// let input = "{\"a\": 1}\n{\"b\": 2}\n{\"a\": 3}\n";
+/// let filter = serde_path::path!(.a?);
+/// let values: Vec<u32> = filter_each(Records::new(input.as_bytes()), filter)
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(values, [1, 3]);
+/// ```
+pub fn filter_each<I, V, F, T>(records: I, filter: F) -> FilterEach<I::IntoIter, F, T>
+where
+    I: IntoIterator<Item = Result<V, serde_json::Error>>,
+    V: Deref<Target = RawValue>,
+    F: for<'de> FilterChain<'de> + Clone,
+    T: DeserializeOwned,
+{
+    FilterEach {
+        records: records.into_iter(),
+        filter,
+        index: 0,
+        output: PhantomData,
+    }
+}
+
+pub struct FilterEach<I, F, T> {
+    records: I,
+    filter: F,
+    index: usize,
+    output: PhantomData<fn() -> T>,
+}
+
+impl<I, V, F, T> Iterator for FilterEach<I, F, T>
+where
+    I: Iterator<Item = Result<V, serde_json::Error>>,
+    V: Deref<Target = RawValue>,
+    F: for<'de> FilterChain<'de> + Clone,
+    T: DeserializeOwned,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let index = self.index;
+            self.index += 1;
+            let value = self
+                .records
+                .next()?
+                .and_then(|raw| self.filter.clone().filter_opt(PhantomData::<T>, &*raw));
+            match value {
+                Ok(Some(value)) => return Some(Ok(value)),
+                Ok(None) => continue,
+                Err(inner) => return Some(Err(Error { index, inner })),
+            }
+        }
+    }
+}
+
+/// Writes the output of a filter for each value, one per line.
+pub struct Writer<W, Fmt = CompactFormatter> {
+    writer: W,
+    formatter: Fmt,
+    seq: bool,
+    raw: bool,
+    stream: bool,
+    keep: bool,
+    buf: Vec<u8>,
+    /// a failure writing an output, which the filter only sees as a custom error.
+    io_error: Option<io::Error>,
+}
+
+impl<W: io::Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_formatter(writer, CompactFormatter)
+    }
+}
+
+impl<W, Fmt> Writer<W, Fmt>
+where
+    W: io::Write,
+    Fmt: Formatter + Clone,
+{
+    pub fn with_formatter(writer: W, formatter: Fmt) -> Self {
+        Writer {
+            writer,
+            formatter,
+            seq: false,
+            raw: false,
+            stream: false,
+            keep: false,
+            buf: Vec::new(),
+            io_error: None,
+        }
+    }
+
+    /// Starts every output with a record separator, as an RFC 7464 JSON text sequence.
+    pub fn seq(mut self, seq: bool) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    /// Writes a string output as its contents, without quotes or escapes, like jq's `-r`.
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Writes every element of the filter's output as an output of its own, for a filter with
+    /// multiple outputs, such as `.[]` from [`jq::parse_program`](crate::jq::parse_program).
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Keeps the JSON text of the outputs for [`Writer::output`]. They are otherwise written
+    /// straight to the writer as the filter produces them, unless [`Writer::raw`] needs the
+    /// whole text.
+    pub fn keep_output(mut self, keep: bool) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Writes the output of the filter for one value, if it matches, and returns whether it
+    /// did. The value can be read as it is filtered, from a [`serde_json::Deserializer`].
+    ///
+    /// If the filter fails, the output written before is kept, which may end with a line
+    /// holding part of a value unless the outputs are buffered.
+    pub fn write<'de, F, D>(&mut self, filter: F, value: D) -> Result<bool, serde_json::Error>
+    where
+        F: FilterChain<'de>,
+        D: de::Deserializer<'de, Error = serde_json::Error>,
+    {
+        self.buf.clear();
+        let written = if self.stream {
+            filter
+                .filter_opt(Outputs(&mut *self), value)
+                .map(|written| written.unwrap_or(false))
+        } else {
+            filter
+                .filter_opt(Output(&mut *self), value)
+                .map(|written| written.is_some())
+        };
+        if let Some(err) = self.io_error.take() {
+            return Err(serde_json::Error::io(err));
+        }
+        if written.is_err() {
+            self.buf.clear();
+        }
+        written
+    }
+
+    /// Writes the output in `buf`.
+    fn emit(&mut self) -> io::Result<()> {
+        let string = match self.buf.first() {
+            Some(b'"') if self.raw => Some(serde_json::from_slice::<String>(&self.buf)?),
+            _ => None,
+        };
+        let text = string.as_ref().map_or(&self.buf[..], |s| s.as_bytes());
+        let mut out = Checked {
+            writer: &mut self.writer,
+            error: &mut self.io_error,
+            written: false,
+        };
+        if self.seq {
+            out.write_all(&[RS])?;
+        }
+        out.write_all(text)?;
+        out.write_all(b"\n")
+    }
+
+    /// The JSON text of the last output written by the last call to [`Writer::write`], with
+    /// [`Writer::keep_output`] or [`Writer::raw`]. Empty otherwise, or if it wrote nothing.
+    pub fn output(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// One output of [`Writer::write`].
+struct Output<'a, W, Fmt>(&'a mut Writer<W, Fmt>);
+
+impl<'de, W, Fmt> DeserializeSeed<'de> for Output<'_, W, Fmt>
+where
+    W: io::Write,
+    Fmt: Formatter + Clone,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let writer = self.0;
+        writer.buf.clear();
+        if writer.raw || writer.keep {
+            let ser =
+                serde_json::Serializer::with_formatter(&mut writer.buf, writer.formatter.clone());
+            JsonSer(ser).deserialize(deserializer)?;
+            return writer.emit().map_err(de::Error::custom);
+        }
+        let mut out = Checked {
+            writer: &mut writer.writer,
+            error: &mut writer.io_error,
+            written: false,
+        };
+        if writer.seq {
+            out.write_all(&[RS]).map_err(de::Error::custom)?;
+        }
+        let mut ser = serde_json::Serializer::with_formatter(&mut out, writer.formatter.clone());
+        let written = SerWrapper(&mut ser).deserialize(deserializer);
+        // the next output starts on its own line, after part of one
+        if written.is_ok() || out.written {
+            out.write_all(b"\n").map_err(de::Error::custom)?;
+        }
+        written
+    }
+}
+
+/// The writer of a [`Writer`], keeping the first error for [`Writer::write`].
+struct Checked<'a, W> {
+    writer: &'a mut W,
+    error: &'a mut Option<io::Error>,
+    written: bool,
+}
+
+impl<W: io::Write> Checked<'_, W> {
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        result.map_err(|err| {
+            if err.kind() == io::ErrorKind::Interrupted {
+                return err;
+            }
+            let copy = io::Error::new(err.kind(), err.to_string());
+            self.error.get_or_insert(err);
+            copy
+        })
+    }
+}
+
+impl<W: io::Write> io::Write for Checked<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf);
+        self.written |= written.as_ref().is_ok_and(|&len| len > 0);
+        self.check(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let flushed = self.writer.flush();
+        self.check(flushed)
+    }
+}
+
+/// The outputs of a [`Writer::stream`], whether any was written.
+struct Outputs<'a, W, Fmt>(&'a mut Writer<W, Fmt>);
+
+impl<'de, W, Fmt> DeserializeSeed<'de> for Outputs<'_, W, Fmt>
+where
+    W: io::Write,
+    Fmt: Formatter + Clone,
+{
+    type Value = bool;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<bool, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, W, Fmt> Visitor<'de> for Outputs<'_, W, Fmt>
+where
+    W: io::Write,
+    Fmt: Formatter + Clone,
+{
+    type Value = bool;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a sequence of outputs")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<bool, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut written = false;
+        while seq.next_element_seed(Output(&mut *self.0))?.is_some() {
+            written = true;
+        }
+        Ok(written)
+    }
+}
+
+/// Writes the output of the filter for every value.
+///
+/// When a value fails to parse or to filter, `on_error` decides whether to go on with the
+/// next one. I/O errors always stop.
+pub fn write_each<I, V, F, W, Fmt>(
+    records: I,
+    filter: F,
+    writer: &mut Writer<W, Fmt>,
+    mut on_error: impl FnMut(&Error) -> ControlFlow<()>,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Result<V, serde_json::Error>>,
+    V: Deref<Target = RawValue>,
+    F: for<'de> FilterChain<'de> + Clone,
+    W: io::Write,
+    Fmt: Formatter + Clone,
+{
+    for (index, raw) in records.into_iter().enumerate() {
+        let result = raw.and_then(|raw| writer.write(filter.clone(), &*raw));
+        if let Err(inner) = result {
+            let err = Error { index, inner };
+            if err.inner.is_io() || on_error(&err).is_break() {
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An error reading or filtering one value of a stream.
+#[derive(Debug)]
+pub struct Error {
+    index: usize,
+    inner: serde_json::Error,
+}
+
+impl Error {
+    /// The position of the value in the stream, counting from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn inner(&self) -> &serde_json::Error {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> serde_json::Error {
+        self.inner
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value {}: {}", self.index, self.inner)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use serde_json::{Value, json, value::RawValue};
+
+    use super::{Records, Writer, filter_each, write_each};
+    use crate::jq;
+
+    #[test]
+    fn ndjson() {
+        let input = "{\"a\": 1}\n{\"a\": {\"b\": 2}}\n3\n{\"a\": 4}{\"a\": 5}";
+        let filter = jq::parse(".a").unwrap();
+        let values: Vec<Result<Value, _>> =
+            filter_each(Records::new(input.as_bytes()), filter).collect();
+
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[0].as_ref().unwrap(), &json!(1));
+        assert_eq!(values[1].as_ref().unwrap(), &json!({"b": 2}));
+        assert_eq!(values[2].as_ref().unwrap_err().index(), 2);
+        let rest: Vec<Value> = values[3..]
+            .iter()
+            .map(|v| v.as_ref().unwrap().clone())
+            .collect();
+        assert_eq!(rest, [json!(4), json!(5)]);
+
+        // a value that does not parse ends the stream
+        let input = "1\n{\"a\": \n2\n";
+        let values: Vec<Result<Value, _>> =
+            filter_each(Records::new(input.as_bytes()), crate::Final).collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].as_ref().unwrap_err().index(), 1);
+
+        // or a `StreamDeserializer`, borrowing the values
+        let values = serde_json::Deserializer::from_str("[1, 2] [3]").into_iter();
+        let values: Vec<u32> =
+            filter_each::<_, &serde_json::value::RawValue, _, _>(values, crate::path!(.[-1]))
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(values, [2, 3]);
+    }
+
+    #[test]
+    fn seq() {
+        let input = "\x1e{\"a\": 1}\n\x1e{\"a\": \n\x1e\x1e{\"a\": 3}\n";
+        let values: Vec<Result<u32, _>> =
+            filter_each(Records::seq(input.as_bytes()), crate::path!(.a)).collect();
+
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0].as_ref().unwrap(), &1);
+        assert_eq!(values[1].as_ref().unwrap_err().index(), 1);
+        assert_eq!(values[2].as_ref().unwrap(), &3);
+    }
+
+    #[test]
+    fn write() {
+        let input = "{\"a\": [1]}\n{\"b\": 2}\n{\"a\": [3]}\n";
+        let filter = jq::parse(".a").unwrap();
+
+        let mut errors = Vec::new();
+        let mut writer = Writer::new(Vec::new());
+        write_each(
+            Records::new(input.as_bytes()),
+            filter.clone(),
+            &mut writer,
+            |err| {
+                errors.push(err.index());
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "[1]\n[3]\n"
+        );
+        assert_eq!(errors, [1]);
+
+        let filter = jq::parse(".a[0]").unwrap();
+        let mut writer = Writer::new(Vec::new()).seq(true);
+        write_each(
+            Records::new(input.as_bytes()),
+            filter.clone(),
+            &mut writer,
+            |err| {
+                errors.push(err.index());
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "\x1e1\n\x1e3\n"
+        );
+        assert_eq!(errors, [1, 1]);
+
+        let mut writer = Writer::new(Vec::new());
+        let err = write_each(Records::new(input.as_bytes()), filter, &mut writer, |_| {
+            ControlFlow::Break(())
+        })
+        .unwrap_err();
+        assert_eq!(err.index(), 1);
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn stream() {
+        let (filter, stream) = jq::parse_program(".[] | .a").unwrap();
+        let mut writer = Writer::new(Vec::new()).stream(stream).keep_output(true);
+        let mut de = serde_json::Deserializer::from_reader(&br#"[{"a": 1}, {}, {"a": [2]}]"#[..]);
+        assert!(writer.write(filter.clone(), &mut de).unwrap());
+        assert_eq!(writer.output(), b"[2]");
+        let raw: &RawValue = serde_json::from_str("[]").unwrap();
+        assert!(!writer.write(filter.clone(), raw).unwrap());
+
+        // the outputs before the failing one are kept
+        let raw: &RawValue = serde_json::from_str(r#"[{"a": 3}, 4]"#).unwrap();
+        assert!(writer.write(filter.clone(), raw).is_err());
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "1\nnull\n[2]\n3\n"
+        );
+
+        // written straight through, without keeping the outputs
+        let mut writer = Writer::new(Vec::new()).stream(stream).seq(true);
+        let mut de = serde_json::Deserializer::from_reader(&br#"[{"a": {"b": [1]}}, {}]"#[..]);
+        assert!(writer.write(filter, &mut de).unwrap());
+        assert_eq!(writer.output(), b"");
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "\x1e{\"b\":[1]}\n\x1enull\n"
+        );
+    }
+}
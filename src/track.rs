@@ -0,0 +1,362 @@
+use std::{borrow::Cow, cell::RefCell, fmt};
+
+use serde::de;
+
+use crate::{FilterChain, content::Content, raw::RawValue2};
+
+thread_local! {
+    /// The segments of the failing value, innermost first, while [`filter`] runs.
+    static TRACK: RefCell<Option<Vec<Segment>>> = const { RefCell::new(None) };
+}
+
+/// Like [`FilterChain::filter`], but the error carries the [`Path`] of the value it occurred at.
+///
+/// ```
+/// use serde_path::track;
+///
+/// let json = r#"{"b": {"c": [{"d": 1}, {"e": 2}]}}"#;
+/// let filter = serde_path::path!(.b.c[1].d);
+/// let mut de = serde_json::Deserializer::from_str(json);
+/// let err = track::filter(filter, std::marker::PhantomData::<u32>, &mut de).unwrap_err();
+/// assert_eq!(err.path().to_string(), ".b.c[1]");
+/// assert!(err.to_string().starts_with("at .b.c[1]: missing field `d`"));
+/// ```
+pub fn filter<'de, F, D, S>(
+    filter: F,
+    seed: S,
+    deserializer: D,
+) -> Result<S::Value, Error<D::Error>>
+where
+    F: FilterChain<'de>,
+    D: de::Deserializer<'de>,
+    S: de::DeserializeSeed<'de>,
+{
+    let _restore = Restore(TRACK.replace(Some(Vec::new())));
+    filter.filter(seed, deserializer).map_err(|inner| {
+        let mut segments = TRACK.take().unwrap_or_default();
+        segments.reverse();
+        Error {
+            path: Path(segments),
+            inner,
+        }
+    })
+}
+
+/// Puts back the state of an enclosing [`filter`].
+struct Restore(Option<Vec<Segment>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        TRACK.set(self.0.take());
+    }
+}
+
+/// Records that `result` failed inside `segment`, if the path is being tracked.
+pub(crate) fn at<T, E>(result: Result<T, E>, segment: impl FnOnce() -> Segment) -> Result<T, E> {
+    if result.is_err() {
+        TRACK.with_borrow_mut(|track| {
+            if let Some(track) = track {
+                track.push(segment());
+            }
+        });
+    }
+    result
+}
+
+/// Whether a [`filter`] is recording the path.
+pub(crate) fn enabled() -> bool {
+    TRACK.with_borrow(Option::is_some)
+}
+
+/// Forgets the path of an error that was handled.
+pub(crate) fn clear() {
+    TRACK.with_borrow_mut(|track| {
+        if let Some(track) = track {
+            track.clear();
+        }
+    });
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    /// `[-n]`, counting from the end of the sequence.
+    FromEnd(usize),
+}
+
+impl Segment {
+    /// A map key, as the raw JSON string or a buffered value.
+    pub(crate) fn raw_key(key: &RawValue2) -> Self {
+        let raw = match key {
+            RawValue2::Borrowed(raw) => raw,
+            RawValue2::Owned(raw) => &**raw,
+            RawValue2::Content(key, _) => return Segment::content_key(key),
+            #[cfg(feature = "cbor")]
+            RawValue2::Cbor(raw) => {
+                let key = de::Deserialize::deserialize(*raw).unwrap_or(Content::Unit);
+                return Segment::content_key(&key);
+            }
+        };
+        Segment::Key(serde_json::from_str(raw.get()).unwrap_or_else(|_| raw.get().to_owned()))
+    }
+
+    pub(crate) fn content_key(key: &Content) -> Self {
+        match key.as_str() {
+            Some(key) => Segment::Key(key.to_owned()),
+            None => Segment::Key(serde_json::to_string(key).unwrap_or_default()),
+        }
+    }
+}
+
+/// Where a value is in the input, written like a jq path: `.b.c[1]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if is_identifier(key) => write!(f, ".{key}")?,
+                segment => {
+                    if i == 0 {
+                        f.write_str(".")?;
+                    }
+                    match segment {
+                        Segment::Key(key) => write!(f, "[{key:?}]")?,
+                        Segment::Index(index) => write!(f, "[{index}]")?,
+                        Segment::FromEnd(n) => write!(f, "[-{n}]")?,
+                    }
+                }
+            }
+        }
+        if self.0.is_empty() {
+            f.write_str(".")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// An error from [`filter`], along with the path of the value it occurred at.
+#[derive(Debug)]
+pub struct Error<E> {
+    path: Path,
+    inner: E,
+}
+
+impl<E> Error<E> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.path, self.inner)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+/// A map key, kept for the path when it is a string or a number, and `?` otherwise.
+pub(crate) struct Key<'de>(Option<Cow<'de, str>>);
+
+impl<'de> Key<'de> {
+    /// The next key of the map, only read when the path is being tracked.
+    pub(crate) fn next<M: de::MapAccess<'de>>(map: &mut M) -> Result<Option<Self>, M::Error> {
+        if enabled() {
+            map.next_key()
+        } else {
+            Ok(map.next_key::<de::IgnoredAny>()?.map(|_| Key(None)))
+        }
+    }
+
+    pub(crate) fn segment(&self) -> Segment {
+        Segment::Key(self.0.as_deref().unwrap_or("?").to_owned())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Key<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> de::Visitor<'de> for KeyVisitor {
+    type Value = Key<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map key")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Borrowed(v))))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_owned()))))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v))))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Self::Value, E> {
+        Ok(Key(Some(Cow::Owned(v.to_string()))))
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Self::Value, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Key(None))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        de::Visitor::visit_seq(de::IgnoredAny, seq).map(|_| Key(None))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        de::Visitor::visit_map(de::IgnoredAny, map).map(|_| Key(None))
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        de::Visitor::visit_enum(de::IgnoredAny, data).map(|_| Key(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, marker::PhantomData};
+
+    use serde_json::json;
+
+    use super::Segment;
+    use crate::{
+        FilterChain, FromEnd, hlist,
+        map::{Map, MapValues, Recurse},
+        optional::Optional,
+    };
+
+    #[test]
+    fn track() {
+        fn path<'de, F, T>(json: &'de str, filter: F) -> String
+        where
+            T: serde::Deserialize<'de> + std::fmt::Debug,
+            F: FilterChain<'de>,
+        {
+            let mut de = serde_json::Deserializer::from_str(json);
+            let err = super::filter(filter, PhantomData::<T>, &mut de).unwrap_err();
+            assert!(err.to_string().starts_with(&format!("at {}: ", err.path())));
+            err.path().to_string()
+        }
+
+        let json = json!({
+            "a": [{"x": 1}, {"x": 2}, {"y": 3}],
+            "b": {"p": {"x": 1}, "q y": {"x": "s"}},
+            "c": {"m": {"n": "x"}, "n": 1},
+        })
+        .to_string();
+
+        assert_eq!(path::<_, u32>(&json, hlist!["a", 2, "x"]), ".a[2]");
+        assert_eq!(
+            path::<_, u32>(&json, hlist!["a", FromEnd::new(1).unwrap(), "x"]),
+            ".a[-1]"
+        );
+        assert_eq!(
+            path::<_, Vec<u32>>(&json, hlist!["a", Map(hlist!["x"])]),
+            ".a[2]"
+        );
+        assert_eq!(
+            path::<_, HashMap<String, u32>>(&json, hlist!["b", MapValues(hlist!["x"])]),
+            r#".b["q y"].x"#
+        );
+        assert_eq!(
+            path::<_, Vec<u32>>(&json, hlist!["c", Recurse::new(Optional("n"))]),
+            ".c.m.n"
+        );
+        assert_eq!(path::<_, u32>(&json, hlist![0]), ".");
+
+        let filter = crate::jq::parse("[.a[] | .x]").unwrap();
+        assert_eq!(path::<_, Vec<u32>>(&json, filter), ".a[2]");
+        let filter = crate::jq::parse(".b.p | .z").unwrap();
+        assert_eq!(path::<_, u32>(&json, filter), ".b.p");
+        let filter = crate::jq::parse("[.c | .. | .n?]").unwrap();
+        assert_eq!(path::<_, Vec<u32>>(&json, filter), ".c.m.n");
+
+        let mut de = serde_json::Deserializer::from_str(&json);
+        let err = super::filter(hlist!["a", 1, "y"], PhantomData::<u32>, &mut de).unwrap_err();
+        assert_eq!(
+            err.path().segments(),
+            [Segment::Key("a".into()), Segment::Index(1)]
+        );
+    }
+}